@@ -0,0 +1,141 @@
+//! Throughput of sending a large static file over loopback, with
+//! `sendfile(2)`, through the buffered file body, and the way files were
+//! served before file bodies: read into a `String` and written in one go.
+//!
+//! Run with `cargo bench --bench sendfile`; `YAW_BENCH_FILE_MB` sets the
+//! file size and `YAW_BENCH_ROUNDS` how often each path sends it.
+
+use std::{
+    env, fs,
+    future::Future,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+use yaw::http::{body::ResponseBody, response::Response};
+
+fn setting(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Sends one response over a fresh connection and waits for the client to
+/// read all of it, returning how long that took and how much was read.
+async fn round<F, Fut>(send: F) -> (Duration, u64)
+where
+    F: FnOnce(TcpStream) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let mut buffer = vec![0; 256 * 1024];
+        let mut received = 0;
+        loop {
+            match stream.read(&mut buffer).await.unwrap() {
+                0 => return received,
+                n => received += n as u64,
+            }
+        }
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let start = Instant::now();
+    send(stream).await;
+    let received = client.await.unwrap();
+    (start.elapsed(), received)
+}
+
+async fn file_response(path: &Path, length: u64) -> Response {
+    let mut response = Response::new();
+    response.set_response_body(ResponseBody::File {
+        file: tokio::fs::File::open(path).await.unwrap(),
+        offset: 0,
+        length,
+    });
+    response
+}
+
+async fn measure<F, Fut>(name: &str, rounds: u64, mut send: F)
+where
+    F: FnMut(TcpStream) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut elapsed = Duration::ZERO;
+    let mut bytes = 0;
+    for _ in 0..rounds {
+        let (time, received) = round(&mut send).await;
+        elapsed += time;
+        bytes += received;
+    }
+    let megabytes = bytes as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<24} {:>10.1} MB/s  ({:.0} MB in {:.2?})",
+        name,
+        megabytes / elapsed.as_secs_f64(),
+        megabytes,
+        elapsed
+    );
+}
+
+fn main() {
+    let size = setting("YAW_BENCH_FILE_MB", 256) * 1024 * 1024;
+    let rounds = setting("YAW_BENCH_ROUNDS", 5);
+    let path: PathBuf =
+        env::temp_dir().join(format!("yaw-bench-sendfile-{}.txt", std::process::id()));
+    let line = b"the quick brown fox jumps over the lazy dog 0123456789\n";
+    fs::write(
+        &path,
+        line.iter()
+            .cycle()
+            .take(size as usize)
+            .copied()
+            .collect::<Vec<u8>>(),
+    )
+    .unwrap();
+
+    println!(
+        "sending a {} MB file {} times over loopback",
+        size / (1024 * 1024),
+        rounds
+    );
+    Runtime::new().unwrap().block_on(async {
+        let file = path.as_path();
+        measure("sendfile", rounds, |mut stream| async move {
+            file_response(file, size)
+                .await
+                .send_to_tcp(&mut stream, "HTTP/1.1")
+                .await
+                .unwrap();
+        })
+        .await;
+        measure("buffered file body", rounds, |mut stream| async move {
+            file_response(file, size)
+                .await
+                .send_to(&mut stream, "HTTP/1.1")
+                .await
+                .unwrap();
+        })
+        .await;
+        measure("read_to_string + write", rounds, |mut stream| async move {
+            // The whole response formatted into one string and written at
+            // once, as `send_to` did. It used a single `write`, which sends
+            // only part of a file this size, so this writes all of it.
+            let mut response = Response::new();
+            response.set_body(fs::read_to_string(file).unwrap());
+            stream
+                .write_all(response.to_string().as_bytes())
+                .await
+                .unwrap();
+        })
+        .await;
+    });
+    fs::remove_file(&path).unwrap();
+}
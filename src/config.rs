@@ -1,32 +1,193 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use crate::http::{request::Request, response::Response};
+use crate::{
+    embed::{EmbeddedFile, EmbeddedSource},
+    file_cache::{FileCache, FileCacheOptions},
+    http::{
+        autoindex::AutoIndexOptions,
+        caching::CacheRule,
+        compression::CompressionOptions,
+        conditional::ETagKind,
+        errors::ErrorTemplates,
+        request::{Request, RequestLimits},
+        response::{Response, Status},
+        sse::EventSender,
+        typed_headers::CacheControl,
+        websocket::{WebSocket, WebSocketLimits},
+    },
+    hub::Hub,
+    mount::{Mount, MountOptions},
+};
+
+pub type WebSocketHandler = fn(request: &Request, socket: WebSocket, hub: &Arc<Hub>);
+
+/// What a handler route does with a request whose path differs from the
+/// route only by a trailing slash, like `/about/` for a route `/about`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrailingSlash {
+    /// Only the path as registered matches.
+    Exact,
+    /// Redirects with 301 to the path as registered.
+    Redirect,
+    /// Both paths are served by the route.
+    Ignore,
+}
 
 pub struct Config {
     pub port: u32,
     pub root_path: String,
-    pub index: String,
+    /// Files served for a directory, tried in order. This replaces the
+    /// single `index` field; `set_index` still sets one file, and the
+    /// deprecated `index()` reads the first.
+    pub index_files: Vec<String>,
     pub username: String,
     pub password: String,
-    pub request_handlers: HashMap<String, fn(request: &Request,response: &mut Response)>,
+    pub request_handlers: HashMap<String, fn(request: &Request, response: &mut Response)>,
+    /// Applies to request, SSE and WebSocket handlers alike.
+    pub trailing_slash: TrailingSlash,
+    pub sse_handlers: HashMap<String, fn(request: &Request, events: EventSender)>,
+    pub sse_keep_alive: Duration,
+    pub websocket_handlers: HashMap<String, WebSocketHandler>,
+    pub websocket_limits: WebSocketLimits,
+    /// How many messages each hub subscriber may fall behind before it misses some.
+    pub hub_capacity: usize,
+    pub request_limits: RequestLimits,
+    pub error_templates: ErrorTemplates,
+    pub compression: CompressionOptions,
+    /// The entity tag static files are sent with, alongside `Last-Modified`.
+    pub file_etags: ETagKind,
+    /// `Cache-Control` by path, first match wins; see [`CacheRule`].
+    pub cache_rules: Vec<CacheRule>,
+    pub autoindex: AutoIndexOptions,
+    /// The document served for client-side routes; see [`Config::set_spa_fallback`].
+    pub spa_fallback: Option<String>,
+    /// Directories served under URL prefixes besides `root_path`.
+    pub mounts: Vec<Mount>,
+    /// Keeps static files in memory; see [`Config::enable_file_cache`].
+    pub file_cache: Option<Arc<FileCache>>,
 }
 
-impl Config {
-    pub fn default() -> Config {
+impl Default for Config {
+    fn default() -> Config {
         Config {
             port: 8080,
             root_path: "root/".to_string(),
-            index: "index.html".to_string(),
+            index_files: vec!["index.html".to_string()],
             username: "user".to_string(),
             password: "password".to_string(),
             request_handlers: HashMap::new(),
+            trailing_slash: TrailingSlash::Exact,
+            sse_handlers: HashMap::new(),
+            sse_keep_alive: Duration::from_secs(15),
+            websocket_handlers: HashMap::new(),
+            websocket_limits: WebSocketLimits::default(),
+            hub_capacity: 64,
+            request_limits: RequestLimits::default(),
+            error_templates: ErrorTemplates::default(),
+            compression: CompressionOptions::default(),
+            file_etags: ETagKind::Strong,
+            cache_rules: Vec::new(),
+            autoindex: AutoIndexOptions::default(),
+            spa_fallback: None,
+            mounts: Vec::new(),
+            file_cache: None,
         }
     }
+}
 
+impl Config {
+    /// Serves `index` for directories; see [`Config::set_index_files`].
     pub fn set_index(&mut self, index: &str) {
-        self.index = index.to_string();
+        self.index_files = vec![index.to_string()];
+    }
+
+    /// The first index file, which the `index` field this replaced held.
+    #[deprecated(note = "use `Config::index_files`")]
+    pub fn index(&self) -> &str {
+        self.index_files.first().map_or("", String::as_str)
+    }
+
+    /// Serves the first of `index_files` that exists for directories. A
+    /// directory requested without a trailing slash is redirected to add it.
+    pub fn set_index_files(&mut self, index_files: &[&str]) {
+        self.index_files = index_files.iter().map(|index| index.to_string()).collect();
+    }
+    pub fn set_request_limits(&mut self, request_limits: RequestLimits) {
+        self.request_limits = request_limits;
+    }
+
+    /// Sets the HTML template for error pages; see [`ErrorTemplates`] for placeholders.
+    pub fn set_error_template(&mut self, template: &str) {
+        self.error_templates.html = template.to_string();
+    }
+
+    /// Sets the HTML template for errors with `status`, overriding the general template.
+    pub fn set_error_page(&mut self, status: Status, template: &str) {
+        self.error_templates
+            .html_by_status
+            .insert(status.as_u16(), template.to_string());
+    }
+
+    /// Sends `cache_control` with responses for paths matching `pattern`,
+    /// such as `*.html` or `/assets/**`, unless a handler set its own.
+    /// Rules are tried in the order they were added.
+    pub fn add_cache_rule(&mut self, pattern: &str, cache_control: CacheControl) {
+        self.cache_rules.push(CacheRule::new(pattern, cache_control));
     }
-    pub fn add_request_handler(&mut self, path: &str,handler: fn(request: &Request,response: &mut Response)) {
+
+    /// Lists directories requested under the path prefix `path`, such as
+    /// `/downloads/`, instead of answering 404.
+    pub fn add_autoindex(&mut self, path: &str) {
+        self.autoindex.paths.push(path.to_string());
+    }
+
+    /// Serves the files in `directory` under the URL prefix `prefix`. The
+    /// mount with the longest prefix matching a path serves it; files it has
+    /// take precedence over handlers, other paths under the prefix fall back
+    /// to them. Paths outside every mount are served from `root_path`.
+    pub fn add_mount(&mut self, prefix: &str, directory: &str, options: MountOptions) {
+        self.mounts.push(Mount::new(prefix, directory, options));
+    }
+
+    /// Serves assets compiled into the binary under `prefix`, as included
+    /// with [`include_assets!`](crate::include_assets).
+    pub fn add_embedded_mount(&mut self, prefix: &str, files: &'static [EmbeddedFile], options: MountOptions) {
+        self.mounts.push(Mount::with_source(
+            prefix,
+            Arc::new(EmbeddedSource::new(files)),
+            options,
+        ));
+    }
+
+    /// Serves files from `root_path` and directory mounts out of memory once
+    /// they have been read, with every connection sharing one cache.
+    pub fn enable_file_cache(&mut self, options: FileCacheOptions) {
+        self.file_cache = Some(FileCache::new(options));
+    }
+
+    /// Serves `file`, such as `index.html`, for GETs that match neither a
+    /// handler nor a file, when the client accepts `text/html`. Paths with an
+    /// extension, like `/app.js`, are taken to be missing assets and still 404.
+    pub fn set_spa_fallback(&mut self, file: &str) {
+        self.spa_fallback = Some(file.to_string());
+    }
+
+    pub fn add_request_handler(&mut self, path: &str, handler: fn(request: &Request, response: &mut Response)) {
         self.request_handlers.insert(path.to_string(), handler);
     }
-}
\ No newline at end of file
+
+    /// Serves Server-Sent Events on `path`. The handler is called once per
+    /// client and typically spawns a task that sends events until
+    /// [`EventSender::send`] reports the client has disconnected.
+    pub fn add_sse_handler(&mut self, path: &str, handler: fn(request: &Request, events: EventSender)) {
+        self.sse_handlers.insert(path.to_string(), handler);
+    }
+
+    /// Accepts WebSocket connections on `path`. The handler is called once the
+    /// handshake has completed and typically spawns a task that serves the
+    /// connection until [`WebSocket::recv`] returns `None`. `hub` is the
+    /// server's [`Hub`], for subscribing the connection to channels.
+    pub fn add_websocket_handler(&mut self, path: &str, handler: WebSocketHandler) {
+        self.websocket_handlers.insert(path.to_string(), handler);
+    }
+}
@@ -0,0 +1,294 @@
+//! Static assets compiled into the binary.
+//!
+//! A build script turns a directory into Rust source with [`Embed`]:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     yaw::embed::Embed::new("site").write_to_out_dir("site").unwrap();
+//! }
+//! ```
+//!
+//! and the server mounts the result with [`include_assets!`]:
+//!
+//! ```ignore
+//! config.add_embedded_mount("/", yaw::include_assets!("site"), MountOptions::default());
+//! ```
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    env,
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use brotli::CompressorWriter;
+use flate2::{write::GzEncoder, Compression};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    file_source::{FileSource, SourceFile, SourceMetadata},
+    http::{
+        autoindex::Entry,
+        compression::{CompressionOptions, ContentCoding},
+        mime,
+    },
+};
+
+/// Includes the assets a build script generated with
+/// [`Embed::write_to_out_dir`] under `name`, as a
+/// `&'static [EmbeddedFile]`.
+#[macro_export]
+macro_rules! include_assets {
+    ($name:expr) => {{
+        static FILES: &[$crate::embed::EmbeddedFile] = include!(concat!(env!("OUT_DIR"), "/", $name, ".rs"));
+        FILES
+    }};
+}
+
+/// A file compiled into the binary, with everything a response needs
+/// worked out at build time.
+#[derive(Debug)]
+pub struct EmbeddedFile {
+    /// The path below the mount, starting with `/`.
+    pub path: &'static str,
+    pub content: &'static [u8],
+    pub content_type: &'static str,
+    /// A hash of the content, for entity tags.
+    pub version: &'static str,
+    /// When the file was last modified before the build, in seconds since
+    /// the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+/// Serves [`EmbeddedFile`]s. Precompressed variants are files of their own,
+/// like `/app.js.br` next to `/app.js`, found the same way as on disk.
+pub struct EmbeddedSource {
+    files: HashMap<&'static str, &'static EmbeddedFile>,
+    directories: BTreeSet<String>,
+}
+
+impl EmbeddedSource {
+    pub fn new(files: &'static [EmbeddedFile]) -> EmbeddedSource {
+        let mut directories = BTreeSet::new();
+        for file in files {
+            let mut path = file.path;
+            while let Some((parent, _)) = path.rsplit_once('/') {
+                directories.insert(parent.to_string());
+                path = parent;
+            }
+        }
+        EmbeddedSource {
+            files: files.iter().map(|file| (file.path, file)).collect(),
+            directories,
+        }
+    }
+
+    fn file(&self, path: &str) -> io::Result<&'static EmbeddedFile> {
+        self.files
+            .get(path)
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} is not embedded", path)))
+    }
+
+    fn file_metadata(file: &EmbeddedFile) -> SourceMetadata {
+        SourceMetadata {
+            is_dir: false,
+            len: file.content.len() as u64,
+            modified: file.modified.map(|modified| UNIX_EPOCH + Duration::from_secs(modified)),
+            version: file.version.to_string(),
+        }
+    }
+}
+
+impl FileSource for EmbeddedSource {
+    fn metadata(&self, path: &str) -> io::Result<SourceMetadata> {
+        let directory = path.trim_end_matches('/');
+        if self.directories.contains(directory) {
+            return Ok(SourceMetadata {
+                is_dir: true,
+                len: 0,
+                modified: None,
+                version: String::new(),
+            });
+        }
+        self.file(path).map(EmbeddedSource::file_metadata)
+    }
+
+    fn open(&self, path: &str) -> io::Result<(SourceFile, SourceMetadata)> {
+        let file = self.file(path)?;
+        Ok((SourceFile::Static(file.content), EmbeddedSource::file_metadata(file)))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        let directory = path.trim_end_matches('/');
+        if !self.directories.contains(directory) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not embedded", path)));
+        }
+        let prefix = format!("{}/", directory);
+        let subdirectories = self
+            .directories
+            .iter()
+            .filter_map(|subdirectory| subdirectory.strip_prefix(&prefix))
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .map(|name| Entry {
+                name: name.to_string(),
+                is_dir: true,
+                size: 0,
+                modified: None,
+            });
+        let files = self
+            .files
+            .values()
+            .filter_map(|file| Some((file.path.strip_prefix(&prefix)?, file)))
+            .filter(|(name, _)| !name.contains('/'))
+            .map(|(name, file)| Entry {
+                name: name.to_string(),
+                is_dir: false,
+                size: file.content.len() as u64,
+                modified: file.modified,
+            });
+        Ok(subdirectories.chain(files).collect())
+    }
+
+    fn content_type(&self, path: &str) -> &'static str {
+        self.files.get(path).map_or_else(|| mime::from_path(path), |file| file.content_type)
+    }
+}
+
+/// Generates the Rust source for embedding a directory, from a build script.
+pub struct Embed {
+    directory: PathBuf,
+    precompressed: Vec<ContentCoding>,
+    compression: CompressionOptions,
+}
+
+impl Embed {
+    /// Embeds every file below `directory`, hidden ones included, with
+    /// brotli and gzip variants of those worth compressing.
+    pub fn new(directory: impl AsRef<Path>) -> Embed {
+        let compression = CompressionOptions::default();
+        Embed {
+            directory: directory.as_ref().to_path_buf(),
+            precompressed: compression.precompressed.clone(),
+            compression,
+        }
+    }
+
+    /// The codings to add precompressed variants in; empty adds none.
+    /// Codings without a file extension are skipped.
+    pub fn precompress(mut self, codings: &[ContentCoding]) -> Embed {
+        self.precompressed = codings.to_vec();
+        self
+    }
+
+    /// Writes `$OUT_DIR/{name}.rs` for [`include_assets!`] and tells Cargo
+    /// to run the build script again when the directory changes.
+    pub fn write_to_out_dir(&self, name: &str) -> io::Result<()> {
+        let out_dir = env::var_os("OUT_DIR")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is only set for build scripts"))?;
+        println!("cargo:rerun-if-changed={}", self.directory.display());
+        self.write(&Path::new(&out_dir).join(format!("{}.rs", name)))
+    }
+
+    /// Writes the source to `output`, and the precompressed variants to a
+    /// directory next to it named after it.
+    pub fn write(&self, output: &Path) -> io::Result<()> {
+        let variants = output.with_extension("");
+        let directory = fs::canonicalize(&self.directory)?;
+        let mut files = Vec::new();
+        collect_files(&directory, &mut files)?;
+        files.sort();
+
+        let mut source = String::from("&[\n");
+        for file in files {
+            let relative = file
+                .strip_prefix(&directory)
+                .ok()
+                .and_then(Path::to_str)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not UTF-8", file.display())))?;
+            let path = format!("/{}", relative.replace('\\', "/"));
+            let content = fs::read(&file)?;
+            let content_type = mime::from_path(&path);
+            let modified = fs::metadata(&file)?
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs());
+            push_file(&mut source, &path, &file, &content, content_type, modified);
+
+            if !self.compression.is_compressible_type(content_type.split(';').next().unwrap_or_default()) {
+                continue;
+            }
+            for &coding in &self.precompressed {
+                let extension = match coding.extension() {
+                    Some(extension) => extension,
+                    None => continue,
+                };
+                let compressed = compress(&content, coding)?;
+                if compressed.len() >= content.len() {
+                    continue;
+                }
+                let variant = variants.join(format!("{}.{}", relative, extension));
+                fs::create_dir_all(variant.parent().unwrap_or(&variants))?;
+                fs::write(&variant, &compressed)?;
+                let variant_path = format!("{}.{}", path, extension);
+                push_file(&mut source, &variant_path, &variant, &compressed, content_type, modified);
+            }
+        }
+        source.push_str("]\n");
+        fs::write(output, source)
+    }
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if fs::metadata(&path)?.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn push_file(
+    source: &mut String,
+    path: &str,
+    file: &Path,
+    content: &[u8],
+    content_type: &str,
+    modified: Option<u64>,
+) {
+    let version: String = Sha1::digest(content)[..10].iter().map(|byte| format!("{:02x}", byte)).collect();
+    let _ = writeln!(
+        source,
+        "    yaw::embed::EmbeddedFile {{ path: {:?}, content: include_bytes!({:?}), content_type: {:?}, version: {:?}, modified: {:?} }},",
+        path,
+        file.display().to_string(),
+        content_type,
+        version,
+        modified
+    );
+}
+
+/// Compresses at the highest levels, since it only happens at build time.
+fn compress(content: &[u8], coding: ContentCoding) -> io::Result<Vec<u8>> {
+    match coding {
+        ContentCoding::Brotli => {
+            let mut encoder = CompressorWriter::new(Vec::new(), 4096, 11, 22);
+            encoder.write_all(content)?;
+            Ok(encoder.into_inner())
+        }
+        ContentCoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+            encoder.write_all(content)?;
+            encoder.finish()
+        }
+        ContentCoding::Deflate => Ok(content.to_vec()),
+    }
+}
@@ -0,0 +1,427 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    file_source::{FileSource, SourceFile, SourceMetadata},
+    http::autoindex::Entry,
+};
+
+/// How cached files are noticed to have changed on disk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Invalidation {
+    /// Watches the directories of cached files, so changes are noticed as
+    /// they happen. Falls back to checking mtimes on every request if the
+    /// platform's watcher can't be started.
+    Notify,
+    /// Checks the size and mtime of a cached file when it was last checked
+    /// more than `interval` ago, so changes may go unnoticed for that long.
+    Mtime { interval: Duration },
+}
+
+#[derive(Debug, Clone)]
+pub struct FileCacheOptions {
+    /// The total size of the cached contents; least recently used files are
+    /// dropped to stay below it.
+    pub max_bytes: u64,
+    /// Larger files are read from disk every time.
+    pub max_file_size: u64,
+    pub invalidation: Invalidation,
+}
+
+impl Default for FileCacheOptions {
+    fn default() -> Self {
+        FileCacheOptions {
+            max_bytes: 64 * 1024 * 1024,
+            max_file_size: 1024 * 1024,
+            invalidation: Invalidation::Notify,
+        }
+    }
+}
+
+/// Counts of cache lookups, as returned by [`FileCache::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStats {
+    /// Files opened from memory.
+    pub hits: u64,
+    /// Files read from disk, whether they were cached afterwards or not.
+    pub misses: u64,
+    /// Files dropped to make room for others.
+    pub evictions: u64,
+    /// Files dropped because they changed on disk.
+    pub invalidations: u64,
+    pub entries: u64,
+    pub bytes: u64,
+}
+
+/// The metadata and content of a cached path, or `None` if there is no file
+/// there.
+type Cached = Option<(SourceMetadata, Option<Arc<[u8]>>)>;
+
+struct CacheEntry {
+    /// `None` for files that don't exist, so looking for them again, as for
+    /// precompressed siblings, doesn't touch the disk either.
+    metadata: Option<SourceMetadata>,
+    /// `None` for directories, files over `max_file_size` and files that
+    /// were only looked up.
+    content: Option<Arc<[u8]>>,
+    checked: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<PathBuf, CacheEntry>,
+    /// Entries by `last_used`, least recently used first.
+    recency: BTreeMap<u64, PathBuf>,
+    clock: u64,
+    bytes: u64,
+    watched: HashSet<PathBuf>,
+    /// Counts calls to `invalidate`, so an insert can tell whether an event
+    /// arrived while it checked the file.
+    generation: u64,
+}
+
+impl State {
+    fn touch(&mut self, path: &Path) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.entries.get_mut(path) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = clock;
+            self.recency.insert(clock, path.to_path_buf());
+        }
+    }
+
+    fn remove(&mut self, path: &Path) -> bool {
+        match self.entries.remove(path) {
+            Some(entry) => {
+                self.recency.remove(&entry.last_used);
+                self.bytes -= entry_size(path, &entry);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn entry_size(path: &Path, entry: &CacheEntry) -> u64 {
+    path.as_os_str().len() as u64
+        + entry
+            .content
+            .as_ref()
+            .map_or(0, |content| content.len() as u64)
+}
+
+/// An LRU cache of file contents and metadata, bounded by total bytes.
+///
+/// One cache serves every mount and connection of a server; it is keyed by
+/// file system path, so mounts sharing directories share entries too.
+pub struct FileCache {
+    options: FileCacheOptions,
+    state: Mutex<State>,
+    /// `None` when checking mtimes instead.
+    watcher: Option<Mutex<RecommendedWatcher>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl FileCache {
+    pub fn new(options: FileCacheOptions) -> Arc<FileCache> {
+        Arc::new_cyclic(|cache: &Weak<FileCache>| {
+            let watcher = match options.invalidation {
+                Invalidation::Notify => {
+                    let cache = cache.clone();
+                    notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                        let (Some(cache), Ok(event)) = (cache.upgrade(), event) else {
+                            return;
+                        };
+                        if !matches!(event.kind, EventKind::Access(_)) {
+                            for path in &event.paths {
+                                cache.invalidate(path);
+                            }
+                        }
+                    })
+                    .ok()
+                    .map(Mutex::new)
+                }
+                Invalidation::Mtime { .. } => None,
+            };
+            FileCache {
+                options,
+                state: Mutex::new(State::default()),
+                watcher,
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                evictions: AtomicU64::new(0),
+                invalidations: AtomicU64::new(0),
+            }
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+            entries: state.entries.len() as u64,
+            bytes: state.bytes,
+        }
+    }
+
+    /// Drops `path`, and whatever is cached below it if it is a directory.
+    pub fn invalidate(&self, path: &Path) {
+        let mut state = self.state();
+        state.generation += 1;
+        let below: Vec<PathBuf> = state
+            .entries
+            .keys()
+            .filter(|cached| cached.starts_with(path))
+            .cloned()
+            .collect();
+        for cached in below {
+            if state.remove(&cached) {
+                self.invalidations.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.entries.clear();
+        state.recency.clear();
+        state.bytes = 0;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // Every operation leaves the maps consistent, so a panic elsewhere can't leave them broken.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The cached entry for `path` if it is still current.
+    fn lookup(&self, path: &Path) -> Option<Cached> {
+        let cached = {
+            let mut state = self.state();
+            let entry = state.entries.get(path)?;
+            let needs_check = match self.options.invalidation {
+                Invalidation::Notify if self.watcher.is_some() => false,
+                Invalidation::Notify => true,
+                Invalidation::Mtime { interval } => entry.checked.elapsed() >= interval,
+            };
+            let cached = entry
+                .metadata
+                .clone()
+                .map(|metadata| (metadata, entry.content.clone()));
+            if !needs_check {
+                state.touch(path);
+                return Some(cached);
+            }
+            cached
+        };
+        // Stat without holding the lock; the file system may be slow.
+        let unchanged = unchanged(path, cached.as_ref().map(|(metadata, _)| metadata));
+        let mut state = self.state();
+        if !unchanged {
+            if state.remove(path) {
+                self.invalidations.fetch_add(1, Ordering::Relaxed);
+            }
+            return None;
+        }
+        if let Some(entry) = state.entries.get_mut(path) {
+            entry.checked = Instant::now();
+        }
+        state.touch(path);
+        Some(cached)
+    }
+
+    fn insert(&self, path: &Path, metadata: Option<SourceMetadata>, content: Option<Arc<[u8]>>) {
+        let entry = CacheEntry {
+            metadata,
+            content,
+            checked: Instant::now(),
+            last_used: 0,
+        };
+        let size = entry_size(path, &entry);
+        if size > self.options.max_bytes {
+            return;
+        }
+        let generation = self.state().generation;
+        if let Some(watcher) = &self.watcher {
+            // Watching the directory sees the file being replaced, not just written.
+            let directory = match &entry.metadata {
+                Some(metadata) if metadata.is_dir => path,
+                _ => path.parent().unwrap_or(path),
+            };
+            let mut state = self.state();
+            if state.watched.insert(directory.to_path_buf()) {
+                drop(state);
+                let mut watcher = watcher.lock().unwrap_or_else(|e| e.into_inner());
+                if watcher
+                    .watch(directory, RecursiveMode::NonRecursive)
+                    .is_err()
+                {
+                    self.state().watched.remove(directory);
+                    return;
+                }
+            }
+            // A change between reading the file and watching its directory
+            // raised no event, so make sure the file is still what was read.
+            // Any later change raises one, which the generation check below
+            // sees.
+            if !unchanged(path, entry.metadata.as_ref()) {
+                return;
+            }
+        }
+        let mut state = self.state();
+        if self.watcher.is_some() && state.generation != generation {
+            return;
+        }
+        state.remove(path);
+        while state.bytes + size > self.options.max_bytes {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.remove(&oldest);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        state.bytes += size;
+        state.entries.insert(path.to_path_buf(), entry);
+        state.touch(path);
+    }
+}
+
+/// The parts of file system metadata that tell whether a file changed.
+struct FsMetadata {
+    is_dir: bool,
+    len: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+impl FsMetadata {
+    /// The metadata of the file at `path`, or `None` if there is none.
+    fn current(path: &Path) -> Option<FsMetadata> {
+        fs::metadata(path).ok().map(|metadata| FsMetadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Whether the file at `path` is still as cached, missing files included.
+fn unchanged(path: &Path, cached: Option<&SourceMetadata>) -> bool {
+    match (FsMetadata::current(path), cached) {
+        (Some(current), Some(cached)) => {
+            current.is_dir == cached.is_dir
+                && current.len == cached.len
+                && current.modified == cached.modified
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// A [`FileSource`] answering from a [`FileCache`] where it can. Sources
+/// that aren't backed by the file system, and requests without a cache, go
+/// straight to `inner`.
+pub struct CachedSource<'a> {
+    inner: &'a dyn FileSource,
+    cache: Option<&'a FileCache>,
+}
+
+impl<'a> CachedSource<'a> {
+    pub fn new(inner: &'a dyn FileSource, cache: Option<&'a FileCache>) -> CachedSource<'a> {
+        CachedSource { inner, cache }
+    }
+
+    /// The cache and the absolute path of `path`. Keys must be absolute, as
+    /// the paths of watcher events are.
+    fn cache_for(&self, path: &str) -> Option<(&'a FileCache, PathBuf)> {
+        let cache = self.cache?;
+        let file_path = std::path::absolute(self.inner.file_path(path)?).ok()?;
+        Some((cache, file_path))
+    }
+}
+
+impl FileSource for CachedSource<'_> {
+    fn metadata(&self, path: &str) -> io::Result<SourceMetadata> {
+        let Some((cache, file_path)) = self.cache_for(path) else {
+            return self.inner.metadata(path);
+        };
+        match cache.lookup(&file_path) {
+            Some(Some((metadata, _))) => return Ok(metadata),
+            Some(None) => return Err(io::ErrorKind::NotFound.into()),
+            None => {}
+        }
+        // Contents are cached when files are opened.
+        match self.inner.metadata(path) {
+            Ok(metadata) => {
+                cache.insert(&file_path, Some(metadata.clone()), None);
+                Ok(metadata)
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    cache.insert(&file_path, None, None);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn open(&self, path: &str) -> io::Result<(SourceFile, SourceMetadata)> {
+        let Some((cache, file_path)) = self.cache_for(path) else {
+            return self.inner.open(path);
+        };
+        match cache.lookup(&file_path) {
+            Some(Some((metadata, Some(content)))) => {
+                cache.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok((SourceFile::Shared(content), metadata));
+            }
+            Some(None) => return Err(io::ErrorKind::NotFound.into()),
+            _ => {}
+        }
+        cache.misses.fetch_add(1, Ordering::Relaxed);
+        let (file, metadata) = self.inner.open(path)?;
+        if metadata.is_dir || metadata.len > cache.options.max_file_size {
+            return Ok((file, metadata));
+        }
+        let content: Arc<[u8]> = match file {
+            SourceFile::File(mut file) => {
+                let mut content = Vec::with_capacity(metadata.len as usize);
+                io::Read::read_to_end(&mut file, &mut content)?;
+                content.into()
+            }
+            file => return Ok((file, metadata)),
+        };
+        // A file that changed while being read is sent as read, but not kept.
+        if content.len() as u64 == metadata.len {
+            cache.insert(&file_path, Some(metadata.clone()), Some(content.clone()));
+        }
+        Ok((SourceFile::Shared(content), metadata))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        self.inner.read_dir(path)
+    }
+
+    fn content_type(&self, path: &str) -> &'static str {
+        self.inner.content_type(path)
+    }
+
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        self.inner.file_path(path)
+    }
+}
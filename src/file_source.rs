@@ -0,0 +1,148 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::fs::File;
+
+use crate::http::{autoindex::Entry, body::ResponseBody, mime};
+
+/// What a [`FileSource`] knows about one of its paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceMetadata {
+    pub is_dir: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// Changes whenever the content does; entity tags are made from it.
+    pub version: String,
+}
+
+/// The content of an opened file.
+pub enum SourceFile {
+    File(fs::File),
+    Static(&'static [u8]),
+    /// Content held in memory, such as by a file cache.
+    Shared(Arc<[u8]>),
+}
+
+impl SourceFile {
+    /// `length` bytes starting at `offset`, as a response body.
+    pub fn into_body(self, offset: u64, length: u64) -> ResponseBody {
+        match self {
+            SourceFile::File(file) => ResponseBody::File {
+                file: File::from_std(file),
+                offset,
+                length,
+            },
+            SourceFile::Static(content) => ResponseBody::Full(content[offset as usize..][..length as usize].to_vec()),
+            SourceFile::Shared(content) => ResponseBody::Full(content[offset as usize..][..length as usize].to_vec()),
+        }
+    }
+
+    /// Like [`SourceFile::into_body`], for taking several bodies from one file.
+    pub fn body(&self, offset: u64, length: u64) -> io::Result<ResponseBody> {
+        let file = match self {
+            SourceFile::File(file) => SourceFile::File(file.try_clone()?),
+            SourceFile::Static(content) => SourceFile::Static(content),
+            SourceFile::Shared(content) => SourceFile::Shared(content.clone()),
+        };
+        Ok(file.into_body(offset, length))
+    }
+}
+
+/// Where a mount's files come from, such as a directory or assets compiled
+/// into the binary. Paths are relative to the mount and start with `/`.
+pub trait FileSource: Send + Sync {
+    fn metadata(&self, path: &str) -> io::Result<SourceMetadata>;
+
+    fn open(&self, path: &str) -> io::Result<(SourceFile, SourceMetadata)>;
+
+    /// Every entry of the directory at `path`, hidden ones included.
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>>;
+
+    /// The `Content-Type` for `path`, by default from its extension.
+    fn content_type(&self, path: &str) -> &'static str {
+        mime::from_path(path)
+    }
+
+    /// The file system path holding `path`, if there is one. Only such
+    /// files are kept in a [`FileCache`](crate::file_cache::FileCache).
+    fn file_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Files in a directory of the file system.
+pub struct FsSource {
+    directory: String,
+}
+
+impl FsSource {
+    pub fn new(directory: &str) -> FsSource {
+        FsSource {
+            directory: directory.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}{}", self.directory, path)
+    }
+
+    fn source_metadata(metadata: &fs::Metadata) -> SourceMetadata {
+        let modified = metadata.modified().ok();
+        let nanos = modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos());
+        SourceMetadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified,
+            version: format!("{:x}-{:x}", metadata.len(), nanos),
+        }
+    }
+}
+
+impl FileSource for FsSource {
+    fn metadata(&self, path: &str) -> io::Result<SourceMetadata> {
+        fs::metadata(self.path(path)).map(|metadata| Self::source_metadata(&metadata))
+    }
+
+    fn open(&self, path: &str) -> io::Result<(SourceFile, SourceMetadata)> {
+        let file = fs::File::open(self.path(path))?;
+        let metadata = Self::source_metadata(&file.metadata()?);
+        Ok((SourceFile::File(file), metadata))
+    }
+
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        Some(PathBuf::from(self.path(path)))
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(self.path(path))? {
+            let dir_entry = dir_entry?;
+            // Follows symlinks, so a link to a directory is listed as one.
+            let metadata = match fs::metadata(dir_entry.path()) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            entries.push(Entry {
+                name: dir_entry.file_name().to_string_lossy().into_owned(),
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|modified| modified.as_secs()),
+            });
+        }
+        Ok(entries)
+    }
+}
@@ -1,11 +1,11 @@
-use base64::{engine::general_purpose, Engine};
-
-use super::request::Request;
-
-
-
-pub fn basic_auth_validate(request: &Request, username:  &String, password: &String) -> bool {
-    let encoded_creds: String = general_purpose::STANDARD.encode(username.to_string() + ":" + password);
-    let auth_key = "Basic ".to_string() + encoded_creds.as_str();
-    request.get_header("Authorization") == Some(&auth_key) 
-}
\ No newline at end of file
+use super::{request::Request, typed_headers::Authorization};
+
+pub fn basic_auth_validate(request: &Request, username: &str, password: &str) -> bool {
+    match request.typed_header::<Authorization>() {
+        Some(Authorization::Basic {
+            username: given_username,
+            password: given_password,
+        }) => given_username == username && given_password == password,
+        _ => false,
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    cmp::Ordering,
+    fmt::Write,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use super::{
+    errors::{escape_html, HttpError},
+    request::{encode_path, Request},
+    response::Response,
+    typed_headers::format_http_date,
+};
+
+/// Which directories are listed when requested.
+#[derive(Debug, Clone, Default)]
+pub struct AutoIndexOptions {
+    /// Path prefixes to list directories under, such as `/downloads/`.
+    pub paths: Vec<String>,
+    /// Lists files and directories whose names start with a dot.
+    pub show_hidden: bool,
+}
+
+impl AutoIndexOptions {
+    pub fn is_enabled(&self, path: &str) -> bool {
+        self.paths.iter().any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
+
+/// One file or directory in a listing.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Seconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+/// What a listing is sorted by, from the `sort` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+}
+
+/// Lists `entries` as the response to `request`.
+///
+/// The listing is HTML unless the query has `format=json`. It is sorted by
+/// `sort=name|size|modified` in `order=asc|desc`, by name ascending by
+/// default, with directories always first.
+pub fn listing(request: &Request, mut entries: Vec<Entry>) -> Result<Response, HttpError> {
+    let sort = match request.get_param("sort").map(String::as_str) {
+        Some("size") => SortKey::Size,
+        Some("modified") => SortKey::Modified,
+        _ => SortKey::Name,
+    };
+    let descending = request.get_param("order").is_some_and(|order| order == "desc");
+    entries.sort_by(|a, b| {
+        let ordering = match sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then(if descending { ordering.reverse() } else { ordering })
+    });
+
+    if request.get_param("format").is_some_and(|format| format == "json") {
+        return Response::json(&entries);
+    }
+    Ok(Response::html(render_html(request.get_path(), &entries, sort, descending)))
+}
+
+fn render_html(path: &str, entries: &[Entry], sort: SortKey, descending: bool) -> String {
+    let base = if path.ends_with('/') { path.to_string() } else { format!("{}/", path) };
+    let title = escape_html(&format!("Index of {}", base));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n  <head>\n    <meta charset=\"utf-8\">\n    <title>{0}</title>\n  </head>\n  <body>\n    <h1>{0}</h1>\n    <table>\n      <tr>",
+        title
+    );
+    for (key, heading) in [(SortKey::Name, "Name"), (SortKey::Size, "Size"), (SortKey::Modified, "Last modified")] {
+        // Clicking the current column again reverses the order.
+        let order = if key == sort && !descending { "desc" } else { "asc" };
+        let _ = write!(html, "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>", key.as_str(), order, heading);
+    }
+    html.push_str("</tr>\n");
+    if base != "/" {
+        html.push_str("      <tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in entries {
+        let name = if entry.is_dir { format!("{}/", entry.name) } else { entry.name.clone() };
+        let size = if entry.is_dir { "-".to_string() } else { entry.size.to_string() };
+        let modified = entry
+            .modified
+            .map(|modified| format_http_date(UNIX_EPOCH + Duration::from_secs(modified)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "      <tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            escape_html(&encode_path(&format!("{}{}", base, name))),
+            escape_html(&name),
+            size,
+            modified
+        );
+    }
+    html.push_str("    </table>\n  </body>\n</html>\n");
+    html
+}
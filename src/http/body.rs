@@ -0,0 +1,128 @@
+use std::{fmt, io, pin::Pin};
+
+use futures::{stream, Stream, StreamExt};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+};
+
+use super::CRLF;
+
+/// How much of a file [`ResponseBody::into_stream`] reads at a time.
+const FILE_CHUNK_SIZE: u64 = 16 * 1024;
+
+/// A stream of body chunks. An error ends the response early.
+pub type BodyStream = Pin<Box<dyn Stream<Item = io::Result<Vec<u8>>> + Send>>;
+
+/// The body of a [`Response`](super::response::Response).
+pub enum ResponseBody {
+    /// A body held in memory.
+    Full(Vec<u8>),
+    /// `length` bytes of `file`, starting at `offset`, read as they are sent.
+    File { file: File, offset: u64, length: u64 },
+    /// Chunks produced while the response is sent. Without a length the body
+    /// is sent with chunked `Transfer-Encoding`.
+    Stream { stream: BodyStream, length: Option<u64> },
+}
+
+impl ResponseBody {
+    pub fn empty() -> ResponseBody {
+        ResponseBody::Full(Vec::new())
+    }
+
+    /// The length of the body, if it is known before sending.
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            ResponseBody::Full(content) => Some(content.len() as u64),
+            ResponseBody::File { length, .. } => Some(*length),
+            ResponseBody::Stream { length, .. } => *length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Turns the body into a stream of chunks, e.g. to transform it while it
+    /// is sent. Files are read a chunk at a time.
+    pub fn into_stream(self) -> BodyStream {
+        match self {
+            ResponseBody::Full(content) => Box::pin(stream::iter([Ok(content)])),
+            ResponseBody::File { file, offset, length } => {
+                Box::pin(stream::try_unfold((file, Some(offset), length), |(mut file, seek, remaining)| async move {
+                    if let Some(offset) = seek {
+                        file.seek(io::SeekFrom::Start(offset)).await?;
+                    }
+                    if remaining == 0 {
+                        return Ok(None);
+                    }
+                    let mut chunk = vec![0; remaining.min(FILE_CHUNK_SIZE) as usize];
+                    let read = file.read(&mut chunk).await?;
+                    if read == 0 {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being sent"));
+                    }
+                    chunk.truncate(read);
+                    Ok(Some((chunk, (file, None, remaining - read as u64))))
+                }))
+            }
+            ResponseBody::Stream { stream, .. } => stream,
+        }
+    }
+
+    /// Writes the body with `write_all`, framing a stream of unknown length
+    /// as chunks if `chunked` is set.
+    pub async fn write_to<W: AsyncWrite + Unpin>(self, stream: &mut W, chunked: bool) -> io::Result<()> {
+        match self {
+            ResponseBody::Full(content) => stream.write_all(&content).await,
+            ResponseBody::File {
+                mut file,
+                offset,
+                length,
+            } => {
+                file.seek(io::SeekFrom::Start(offset)).await?;
+                let copied = tokio::io::copy(&mut file.take(length), stream).await?;
+                if copied < length {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being sent"));
+                }
+                Ok(())
+            }
+            ResponseBody::Stream { stream: mut chunks, .. } => {
+                while let Some(chunk) = chunks.next().await {
+                    let chunk = chunk?;
+                    if chunk.is_empty() {
+                        continue;
+                    }
+                    if chunked {
+                        stream.write_all(format!("{:x}{}", chunk.len(), CRLF).as_bytes()).await?;
+                        stream.write_all(&chunk).await?;
+                        stream.write_all(CRLF.as_bytes()).await?;
+                    } else {
+                        stream.write_all(&chunk).await?;
+                    }
+                    // Streams are often live, so don't hold chunks back in a buffer.
+                    stream.flush().await?;
+                }
+                if chunked {
+                    stream.write_all(format!("0{}{}", CRLF, CRLF).as_bytes()).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for ResponseBody {
+    fn default() -> Self {
+        ResponseBody::empty()
+    }
+}
+
+impl fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseBody::Full(content) => write!(f, "Full({} bytes)", content.len()),
+            ResponseBody::File { offset, length, .. } => write!(f, "File({} bytes at {})", length, offset),
+            ResponseBody::Stream { length, .. } => write!(f, "Stream({:?} bytes)", length),
+        }
+    }
+}
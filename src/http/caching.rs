@@ -0,0 +1,90 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{
+    headers,
+    request::Request,
+    response::{Response, Status},
+    typed_headers::{CacheControl, Expires},
+};
+
+/// HTTP/1.0 caches may not handle an `Expires` more than a year ahead.
+const MAX_EXPIRES: u64 = 365 * 24 * 60 * 60;
+
+/// A `Cache-Control` value for the paths matching `pattern`.
+///
+/// A pattern containing `/` is matched against the whole path, otherwise
+/// against the file name only. `*` matches any characters except `/` and
+/// `**` matches any characters, so `*.html` covers every HTML file and
+/// `/assets/**` everything below `/assets/`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheRule {
+    pub pattern: String,
+    pub cache_control: CacheControl,
+}
+
+impl CacheRule {
+    pub fn new(pattern: &str, cache_control: CacheControl) -> CacheRule {
+        CacheRule {
+            pattern: pattern.to_string(),
+            cache_control,
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        if self.pattern.contains('/') {
+            glob_match(self.pattern.as_bytes(), path.as_bytes())
+        } else {
+            let file_name = path.rsplit('/').next().unwrap_or(path);
+            glob_match(self.pattern.as_bytes(), file_name.as_bytes())
+        }
+    }
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        [b'*', rest @ ..] => {
+            let segment = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment).any(|skip| glob_match(rest, &text[skip..]))
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Adds the `Cache-Control` of the first rule matching `path`, the path of
+/// the file or handler that served the request,
+/// unless the response already has one, as set by a handler. Only
+/// successful and `304 Not Modified` responses are cached, so others are
+/// left alone.
+///
+/// HTTP/1.0 caches don't know `Cache-Control`, so those clients also get an
+/// `Expires` derived from `max-age`, or one in the past for `no-cache` and
+/// `no-store`.
+pub fn apply(rules: &[CacheRule], path: &str, request: &Request, response: &mut Response) {
+    let status = *response.get_status_code();
+    if !status.is_success() && status != Status::NotModified {
+        return;
+    }
+    if response.get_header(headers::CACHE_CONTROL).is_none() {
+        match rules.iter().find(|rule| rule.matches(path)) {
+            Some(rule) => response.set_typed_header(&rule.cache_control),
+            None => return,
+        }
+    }
+    if request.get_http_version() != "HTTP/1.0" || response.get_header(headers::EXPIRES).is_some() {
+        return;
+    }
+    let cache_control = match response.typed_header::<CacheControl>() {
+        Some(cache_control) => cache_control,
+        None => return,
+    };
+    let expires = if cache_control.no_cache() || cache_control.no_store() {
+        UNIX_EPOCH
+    } else if let Some(max_age) = cache_control.max_age() {
+        SystemTime::now() + Duration::from_secs(max_age.min(MAX_EXPIRES))
+    } else {
+        return;
+    };
+    response.set_typed_header(&Expires(expires));
+}
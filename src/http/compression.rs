@@ -0,0 +1,293 @@
+use std::io::{self, Write};
+
+use brotli::CompressorWriter;
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use futures::{stream, StreamExt};
+
+use super::{
+    body::{BodyStream, ResponseBody},
+    headers,
+    response::{Response, Status},
+    typed_headers::{AcceptEncoding, CacheControl, ContentType, TypedHeader},
+};
+
+/// Brotli quality for compressing at request time; higher levels are too slow.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// A content coding the server can apply to a body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl ContentCoding {
+    /// The name used in `Accept-Encoding` and `Content-Encoding`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentCoding::Brotli => "br",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    /// The extension of precompressed files in this coding, like `app.js.br`.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            ContentCoding::Brotli => Some("br"),
+            ContentCoding::Gzip => Some("gz"),
+            ContentCoding::Deflate => None,
+        }
+    }
+}
+
+/// Which responses are compressed at request time.
+#[derive(Debug, Clone)]
+pub struct CompressionOptions {
+    /// Codings to offer, most preferred first. Empty disables compression.
+    pub codings: Vec<ContentCoding>,
+    /// Smaller bodies are sent as they are. Files of unknown length are
+    /// always compressed.
+    pub min_size: u64,
+    /// Media types worth compressing. An entry ending in `/`, such as
+    /// `text/`, matches a whole top-level type. Types with a `+json` or
+    /// `+xml` suffix are always compressed; `text/event-stream` never is.
+    pub mime_types: Vec<String>,
+    /// Codings to look for as precompressed siblings of static files, most
+    /// preferred first. A sibling the client accepts is sent instead of the
+    /// file, so nothing is compressed at request time.
+    pub precompressed: Vec<ContentCoding>,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        CompressionOptions {
+            codings: vec![ContentCoding::Brotli, ContentCoding::Gzip, ContentCoding::Deflate],
+            min_size: 1024,
+            mime_types: [
+                "text/",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "application/wasm",
+                "image/svg+xml",
+                "image/x-icon",
+                "font/ttf",
+                "font/otf",
+            ]
+            .iter()
+            .map(|mime_type| mime_type.to_string())
+            .collect(),
+            precompressed: vec![ContentCoding::Brotli, ContentCoding::Gzip],
+        }
+    }
+}
+
+impl CompressionOptions {
+    pub(crate) fn is_compressible_type(&self, mime_type: &str) -> bool {
+        if mime_type.eq_ignore_ascii_case("text/event-stream") {
+            return false;
+        }
+        mime_type.ends_with("+json")
+            || mime_type.ends_with("+xml")
+            || self.mime_types.iter().any(|eligible| match eligible.strip_suffix('/') {
+                Some(type_) => mime_type.split('/').next() == Some(type_),
+                None => mime_type == eligible,
+            })
+    }
+}
+
+/// Compresses the body of `response` with the coding the client prefers.
+///
+/// Only bodies worth compressing are touched: an eligible `Content-Type`, at
+/// least `min_size` bytes, in memory or from a file, not already encoded, not
+/// a partial or bodiless status and no `Cache-Control: no-transform`. Those
+/// always get `Vary: Accept-Encoding`, since another client may get another
+/// encoding. Streams are left alone, as an encoder would hold back what they
+/// send until it has enough to compress. A compressed body keeps its
+/// `Content-Length` if it was in memory; compressed files are sent without one.
+pub fn compress(response: &mut Response, accept_encoding: Option<&AcceptEncoding>, options: &CompressionOptions) {
+    if !is_eligible(response, options) {
+        return;
+    }
+    add_vary(response);
+    let Some(coding) = negotiate(accept_encoding, options) else {
+        return;
+    };
+
+    let body = match response.take_body() {
+        ResponseBody::Full(content) => {
+            let mut encoder = Encoder::new(coding);
+            let compressed = encoder
+                .compress(&content)
+                .and_then(|mut compressed| {
+                    compressed.extend(encoder.finish()?);
+                    Ok(compressed)
+                })
+                .expect("compressing into memory cannot fail");
+            ResponseBody::Full(compressed)
+        }
+        body @ ResponseBody::File { .. } => compressed_stream(body.into_stream(), coding),
+        body @ ResponseBody::Stream { .. } => body,
+    };
+    response.set_response_body(body);
+    response.add_header(headers::CONTENT_ENCODING, coding.as_str());
+    weaken_etag(response);
+}
+
+/// Gives a 304 for a file the `ETag` and `Vary` its 200 would have been sent
+/// with, had it been compressed for the same request. `content_type` and
+/// `length` describe the file, which the 304 is sent without.
+pub(crate) fn match_not_modified(
+    response: &mut Response,
+    content_type: &str,
+    length: u64,
+    accept_encoding: Option<&AcceptEncoding>,
+    options: &CompressionOptions,
+) {
+    let content_type = ContentType::parse(content_type).ok();
+    if !is_compressible(response, content_type.as_ref().map(ContentType::mime_type), Some(length), options) {
+        return;
+    }
+    add_vary(response);
+    if negotiate(accept_encoding, options).is_some() {
+        weaken_etag(response);
+    }
+}
+
+/// The coding of `options` the client prefers, if it accepts any.
+fn negotiate(accept_encoding: Option<&AcceptEncoding>, options: &CompressionOptions) -> Option<ContentCoding> {
+    let names: Vec<&str> = options.codings.iter().map(ContentCoding::as_str).collect();
+    let name = accept_encoding?.preferred(&names)?;
+    Some(options.codings[names.iter().position(|known| *known == name).unwrap()])
+}
+
+/// A compressed body is a different representation, so a strong validator no
+/// longer applies to it.
+fn weaken_etag(response: &mut Response) {
+    if let Some(etag) = response.get_header(headers::ETAG).filter(|etag| !etag.starts_with("W/")).cloned() {
+        response.add_header(headers::ETAG, &format!("W/{}", etag));
+    }
+}
+
+fn is_eligible(response: &Response, options: &CompressionOptions) -> bool {
+    let status = *response.get_status_code();
+    if status.is_informational()
+        || status == Status::NoContent
+        || status == Status::PartialContent
+        || status == Status::NotModified
+        || response.get_header(headers::CONTENT_RANGE).is_some()
+        || matches!(response.get_body(), ResponseBody::Stream { .. })
+    {
+        return false;
+    }
+    let content_type = response.get_header(headers::CONTENT_TYPE).and_then(|value| ContentType::parse(value).ok());
+    let mime_type = content_type.as_ref().map(ContentType::mime_type);
+    is_compressible(response, mime_type, response.get_body().len(), options)
+}
+
+/// Whether a body of `mime_type` and `length` may be compressed, given the
+/// other headers of `response`.
+fn is_compressible(response: &Response, mime_type: Option<&str>, length: Option<u64>, options: &CompressionOptions) -> bool {
+    if options.codings.is_empty() || response.get_header(headers::CONTENT_ENCODING).is_some() {
+        return false;
+    }
+    let cache_control = response.get_header(headers::CACHE_CONTROL).and_then(|value| CacheControl::parse(value).ok());
+    if cache_control.is_some_and(|cache_control| cache_control.no_transform()) {
+        return false;
+    }
+    mime_type.is_some_and(|mime_type| options.is_compressible_type(mime_type))
+        && length.is_none_or(|length| length >= options.min_size)
+}
+
+/// Adds `Accept-Encoding` to `Vary`, unless it is already covered.
+pub(crate) fn add_vary(response: &mut Response) {
+    let covered = |vary: &str| {
+        vary.split(',')
+            .map(str::trim)
+            .any(|name| name == "*" || name.eq_ignore_ascii_case(headers::ACCEPT_ENCODING))
+    };
+    let vary = match response.get_header(headers::VARY) {
+        None => headers::ACCEPT_ENCODING.to_string(),
+        Some(vary) if covered(vary) => return,
+        Some(vary) => format!("{}, {}", vary, headers::ACCEPT_ENCODING),
+    };
+    response.add_header(headers::VARY, &vary);
+}
+
+fn compressed_stream(chunks: BodyStream, coding: ContentCoding) -> ResponseBody {
+    let compressed = stream::unfold(Some((chunks, Encoder::new(coding))), move |state| async move {
+        let (mut chunks, mut encoder) = state?;
+        loop {
+            let chunk = match chunks.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => return Some((encoder.finish(), None)),
+            };
+            match encoder.compress(&chunk) {
+                // The encoder is still buffering; read on.
+                Ok(compressed) if compressed.is_empty() => continue,
+                Ok(compressed) => return Some((Ok(compressed), Some((chunks, encoder)))),
+                Err(e) => return Some((Err(e), None)),
+            }
+        }
+    });
+    ResponseBody::Stream {
+        stream: Box::pin(compressed),
+        length: None,
+    }
+}
+
+/// A compressor writing into memory, drained after every chunk.
+enum Encoder {
+    Brotli(Box<CompressorWriter<Vec<u8>>>),
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(coding: ContentCoding) -> Encoder {
+        match coding {
+            ContentCoding::Brotli => Encoder::Brotli(Box::new(CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW,
+            ))),
+            ContentCoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            // HTTP's "deflate" is the zlib format, not a raw deflate stream.
+            ContentCoding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+
+    /// Compresses `chunk` and returns the output that is ready.
+    fn compress(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        let writer: &mut dyn Write = match self {
+            Encoder::Brotli(encoder) => encoder.as_mut(),
+            Encoder::Gzip(encoder) => encoder,
+            Encoder::Deflate(encoder) => encoder,
+        };
+        writer.write_all(chunk)?;
+        let output = match self {
+            Encoder::Brotli(encoder) => encoder.get_mut(),
+            Encoder::Gzip(encoder) => encoder.get_mut(),
+            Encoder::Deflate(encoder) => encoder.get_mut(),
+        };
+        Ok(std::mem::take(output))
+    }
+
+    /// Ends the compressed stream and returns the remaining output.
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Brotli(encoder) => Ok(encoder.into_inner()),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    headers,
+    request::{Method, Request},
+    typed_headers::{EntityTag, IfMatch, IfModifiedSince, IfNoneMatch, IfRange, IfUnmodifiedSince},
+};
+
+/// Which entity tag static files are sent with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ETagKind {
+    /// Usable for `If-Match` and `If-Range` as well as caching.
+    Strong,
+    /// For file systems whose timestamps are too coarse to tell edits apart,
+    /// so a tag may stay the same when a file changes within the same tick.
+    Weak,
+    None,
+}
+
+impl ETagKind {
+    /// A tag for a file whose content is identified by `version`, such as
+    /// one derived from its size and modification time.
+    pub fn for_version(&self, version: &str) -> Option<EntityTag> {
+        match self {
+            ETagKind::Strong => Some(EntityTag::strong(version)),
+            ETagKind::Weak => Some(EntityTag::weak(version)),
+            ETagKind::None => None,
+        }
+    }
+}
+
+/// The outcome of evaluating a request's preconditions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precondition {
+    /// Send the representation as usual.
+    Proceed,
+    /// The client's cached copy is current; answer with 304.
+    NotModified,
+    /// A precondition failed; answer with 412.
+    Failed,
+}
+
+/// Seconds since the epoch, since HTTP dates have no finer resolution.
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+/// Evaluates the request's conditional headers against the current
+/// representation, in the order RFC 9110 section 13.2.2 sets out: `If-Match`,
+/// then `If-Unmodified-Since` without it, then `If-None-Match`, then
+/// `If-Modified-Since` without it. Fields that don't parse are ignored.
+pub fn evaluate(request: &Request, etag: Option<&EntityTag>, last_modified: Option<SystemTime>) -> Precondition {
+    if let Some(if_match) = request.typed_header::<IfMatch>() {
+        // `*` matches any current representation, even one without a tag.
+        let matched = match (&if_match, etag) {
+            (IfMatch::Any, _) => true,
+            (IfMatch::Tags(_), Some(etag)) => if_match.matches(etag),
+            (IfMatch::Tags(_), None) => false,
+        };
+        if !matched {
+            return Precondition::Failed;
+        }
+    } else if let (Some(IfUnmodifiedSince(since)), Some(last_modified)) =
+        (request.typed_header::<IfUnmodifiedSince>(), last_modified)
+    {
+        if seconds(last_modified) > seconds(since) {
+            return Precondition::Failed;
+        }
+    }
+
+    let is_read = matches!(request.get_method(), Method::GET | Method::HEAD);
+    if let Some(if_none_match) = request.typed_header::<IfNoneMatch>() {
+        if if_none_match == IfNoneMatch::Any || etag.is_some_and(|etag| if_none_match.matches(etag)) {
+            return if is_read { Precondition::NotModified } else { Precondition::Failed };
+        }
+    } else if let (true, Some(IfModifiedSince(since)), Some(last_modified)) =
+        (is_read, request.typed_header::<IfModifiedSince>(), last_modified)
+    {
+        if seconds(last_modified) <= seconds(since) {
+            return Precondition::NotModified;
+        }
+    }
+    Precondition::Proceed
+}
+
+/// Whether a `Range` header should be honoured: there is no `If-Range`, or
+/// it names the current representation. An entity tag must match strongly
+/// and a date must be exactly the last modification time.
+pub fn if_range_matches(request: &Request, etag: Option<&EntityTag>, last_modified: Option<SystemTime>) -> bool {
+    if request.get_headers().get(headers::IF_RANGE).is_none() {
+        return true;
+    }
+    match request.typed_header::<IfRange>() {
+        Some(IfRange::ETag(tag)) => etag.is_some_and(|etag| tag.strong_eq(etag)),
+        Some(IfRange::Date(date)) => last_modified.is_some_and(|last_modified| seconds(last_modified) == seconds(date)),
+        None => false,
+    }
+}
@@ -1,12 +1,73 @@
-use super::{response::{Response, self}, ParseError, headers};
+use std::{collections::HashMap, error::Error, fmt};
 
+use super::{
+    headers::{self, Headers, InvalidHeader},
+    request::Request,
+    response::{Response, Status},
+    typed_headers::Accept,
+    ParseError,
+};
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Templates for error response bodies.
+///
+/// The HTML and plain-text templates may use `{status}`, `{reason}` and
+/// `{detail}` placeholders. `{detail}` is filled in from
+/// [`HttpError::with_detail`]; the error's message stays in the log.
+#[derive(Debug, Clone)]
+pub struct ErrorTemplates {
+    pub html: String,
+    pub text: String,
+    /// HTML templates for specific status codes, used instead of `html`.
+    pub html_by_status: HashMap<u16, String>,
+}
+
+impl Default for ErrorTemplates {
+    fn default() -> Self {
+        ErrorTemplates {
+            html: "<!DOCTYPE html>\n<html lang=\"en\">\n  <head>\n    <meta charset=\"utf-8\">\n    \
+                   <title>{status} {reason}</title>\n  </head>\n  <body>\n    <h1>{status} {reason}</h1>\n    \
+                   <p>{detail}</p>\n  </body>\n</html>\n"
+                .to_string(),
+            text: "{status} {reason}\n{detail}".to_string(),
+            html_by_status: HashMap::new(),
+        }
+    }
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn render(template: &str, status: Status, detail: &str) -> String {
+    template
+        .replace("{status}", &status.as_u16().to_string())
+        .replace("{reason}", status.reason_phrase())
+        .replace("{detail}", detail)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ClientError {
     BadRequest,
     Unauthorized,
+    Forbidden,
     NotFound,
     MethodNotAllowed,
+    PreconditionFailed,
+    ContentTooLarge,
+    UriTooLong,
+    RangeNotSatisfiable,
+    UpgradeRequired,
+    RequestHeaderFieldsTooLarge,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ServerError {
     InternalServerError,
     MethodNotImplemented,
@@ -14,80 +75,275 @@ pub enum ServerError {
     ServiceUnavailable,
 }
 
-pub enum HttpError {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpErrorKind {
     ClientError(ClientError),
-    ServerError(ServerError)
+    ServerError(ServerError),
+}
+
+/// An error that ends a request with an error response.
+///
+/// The message and source describe what went wrong for the server log; they
+/// are never written to the response. Only the detail, meant for the client,
+/// is rendered into the body. Extra headers, such as `Retry-After`, are added
+/// to the response.
+#[derive(Debug)]
+pub struct HttpError {
+    kind: HttpErrorKind,
+    message: Option<String>,
+    detail: Option<String>,
+    source: Option<Box<dyn Error + Send + Sync>>,
+    headers: Headers,
 }
 
 impl ClientError {
-    pub fn to_response(self) -> Response {
+    pub fn status(&self) -> Status {
         match self {
-            ClientError::BadRequest => {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::InvalidRequest);
-                response
-            },
-            ClientError::Unauthorized => {
-                let mut response = Response::new();
-                response.add_header(
-                    headers::WWW_AUTHENTICATE,
-                    "Basic realm=\"WallyWorld\"",
-                );
-                response.set_status_code(response::Status::Unauthorized);
-                response
-            },
-            ClientError::MethodNotAllowed => {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::MethodNotAllowed);
-                response
-            },
-            ClientError::NotFound => {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::NotFound);
-                response
-            },
+            ClientError::BadRequest => Status::BadRequest,
+            ClientError::Unauthorized => Status::Unauthorized,
+            ClientError::Forbidden => Status::Forbidden,
+            ClientError::NotFound => Status::NotFound,
+            ClientError::MethodNotAllowed => Status::MethodNotAllowed,
+            ClientError::PreconditionFailed => Status::PreconditionFailed,
+            ClientError::ContentTooLarge => Status::ContentTooLarge,
+            ClientError::UriTooLong => Status::UriTooLong,
+            ClientError::RangeNotSatisfiable => Status::RangeNotSatisfiable,
+            ClientError::UpgradeRequired => Status::UpgradeRequired,
+            ClientError::RequestHeaderFieldsTooLarge => Status::RequestHeaderFieldsTooLarge,
         }
     }
+
+    pub fn to_response(self) -> Response {
+        HttpError::from(self).to_response()
+    }
 }
 
 impl ServerError {
+    pub fn status(&self) -> Status {
+        match self {
+            ServerError::InternalServerError => Status::InternalServerError,
+            ServerError::MethodNotImplemented => Status::NotImplemented,
+            ServerError::BadGateway => Status::BadGateway,
+            ServerError::ServiceUnavailable => Status::ServiceUnavailable,
+        }
+    }
+
     pub fn to_response(self) -> Response {
+        HttpError::from(self).to_response()
+    }
+}
+
+impl HttpErrorKind {
+    pub fn status(&self) -> Status {
         match self {
-            ServerError::InternalServerError => {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::InternalServerError);
-                response
-            },
-            ServerError::MethodNotImplemented => {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::NotImplemented);
-                response
-            },
-            ServerError::BadGateway => {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::BadGateway);
-                response
-            },
-            Self::ServiceUnavailable=> {
-                let mut response = Response::new();
-                response.set_status_code(response::Status::ServiceUnavailable);
-                response
-            },
+            HttpErrorKind::ClientError(e) => e.status(),
+            HttpErrorKind::ServerError(e) => e.status(),
         }
     }
 }
 
 impl HttpError {
+    pub fn new(kind: HttpErrorKind) -> HttpError {
+        HttpError {
+            kind,
+            message: None,
+            detail: None,
+            source: None,
+            headers: Headers::new(),
+        }
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> HttpError {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Sets the explanation shown to the client in the error body. Unlike the
+    /// message, it must not mention anything the client shouldn't see.
+    pub fn with_detail(mut self, detail: impl Into<String>) -> HttpError {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<Box<dyn Error + Send + Sync>>) -> HttpError {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Adds a header to the error response. Invalid headers are dropped when
+    /// the response is built.
+    pub fn with_header(mut self, header_key: &str, header_value: &str) -> HttpError {
+        self.headers.append(header_key, header_value);
+        self
+    }
+
+    pub fn kind(&self) -> &HttpErrorKind {
+        &self.kind
+    }
+
+    pub fn status(&self) -> Status {
+        self.kind.status()
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Builds the error response for a client whose `Accept` header is
+    /// unknown, such as one whose request couldn't be parsed. It is
+    /// negotiated as if the header were missing, so the body is plain text.
     pub fn to_response(self) -> Response {
-        match self {
-            Self::ClientError(e) => e.to_response(),
-            Self::ServerError(e) => e.to_response()
+        self.to_negotiated_response(None, &ErrorTemplates::default())
+    }
+
+    /// Builds the error response negotiated with the `Accept` header of
+    /// `request`, for handlers that turn their own errors into responses.
+    pub fn to_response_for(self, request: &Request, templates: &ErrorTemplates) -> Response {
+        self.to_negotiated_response(request.typed_header::<Accept>().as_ref(), templates)
+    }
+
+    /// Builds the error response with the body format the client prefers:
+    /// an HTML page, an RFC 9457 `application/problem+json` document or plain
+    /// text. Only clients that name `text/html` get the page, so the `*/*` of
+    /// command-line tools and HTTP libraries gets problem details. Without an
+    /// `Accept` header, plain text is sent.
+    pub fn to_negotiated_response(self, accept: Option<&Accept>, templates: &ErrorTemplates) -> Response {
+        let status = self.status();
+        let detail = self.detail.clone();
+        let format = match accept {
+            Some(accept) => {
+                let names_html = accept
+                    .media_ranges()
+                    .iter()
+                    .any(|range| range.mime_type.eq_ignore_ascii_case("text/html"));
+                if names_html {
+                    accept.preferred(&["text/html", PROBLEM_JSON, "application/json", "text/plain"])
+                } else {
+                    accept.preferred(&[PROBLEM_JSON, "application/json", "text/plain"])
+                }
+            }
+            None => None,
+        };
+        let mut response = self.to_bodyless_response();
+        let (content_type, body) = match format {
+            Some("text/html") => {
+                let template = templates.html_by_status.get(&status.as_u16()).unwrap_or(&templates.html);
+                let detail = escape_html(detail.as_deref().unwrap_or(""));
+                ("text/html; charset=utf-8", render(template, status, &detail))
+            }
+            Some(PROBLEM_JSON) | Some("application/json") => {
+                let mut problem = serde_json::json!({
+                    "type": "about:blank",
+                    "title": status.reason_phrase(),
+                    "status": status.as_u16(),
+                });
+                if let Some(detail) = detail {
+                    problem["detail"] = detail.into();
+                }
+                (PROBLEM_JSON, problem.to_string())
+            }
+            _ => {
+                let body = render(&templates.text, status, detail.as_deref().unwrap_or(""));
+                ("text/plain; charset=utf-8", body.trim_end().to_string() + "\n")
+            }
+        };
+        response.add_header(headers::CONTENT_TYPE, content_type);
+        response.set_body(body);
+        response
+    }
+
+    fn to_bodyless_response(&self) -> Response {
+        let mut response = Response::new();
+        response.set_status_code(self.status());
+        for (header_key, header_value) in self.headers.iter() {
+            response.append_header(header_key, header_value);
         }
+        if self.status() == Status::Unauthorized && response.get_header(headers::WWW_AUTHENTICATE).is_none() {
+            response.add_header(headers::WWW_AUTHENTICATE, "Basic realm=\"WallyWorld\"");
+        }
+        response
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status())
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status())
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status())?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        let mut source = self.source();
+        while let Some(e) = source {
+            write!(f, ": {}", e)?;
+            source = e.source();
+        }
+        Ok(())
+    }
+}
+
+impl Error for ClientError {}
+
+impl Error for ServerError {}
+
+impl Error for HttpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+impl From<ClientError> for HttpError {
+    fn from(e: ClientError) -> Self {
+        HttpError::new(HttpErrorKind::ClientError(e))
+    }
+}
+
+impl From<ServerError> for HttpError {
+    fn from(e: ServerError) -> Self {
+        HttpError::new(HttpErrorKind::ServerError(e))
     }
 }
 
 impl From<ParseError> for HttpError {
-    fn from(_: ParseError) -> Self {
-        HttpError::ClientError(ClientError::BadRequest)
+    fn from(e: ParseError) -> Self {
+        // Parse errors name the part of the request that is malformed, so they
+        // are safe to tell the client.
+        HttpError::from(ClientError::BadRequest)
+            .with_message(e.to_string())
+            .with_detail(e.to_string())
+    }
+}
+
+impl From<InvalidHeader> for HttpError {
+    fn from(e: InvalidHeader) -> Self {
+        HttpError::from(ServerError::InternalServerError)
+            .with_message("handler set an invalid header")
+            .with_source(e)
     }
-}
\ No newline at end of file
+}
+
+impl From<serde_json::Error> for HttpError {
+    fn from(e: serde_json::Error) -> Self {
+        HttpError::from(ServerError::InternalServerError)
+            .with_message("unable to serialize the response body")
+            .with_source(e)
+    }
+}
@@ -1,49 +1,288 @@
-use std::{collections::HashMap, fmt};
+use std::fmt;
 
-use super::{CRLF, ParseError};
+use super::{typed_headers::TypedHeader, CRLF, ParseError};
 
 type HeaderKey = String;
 type HeaderValue = String;
+
+/// A header field that cannot be written without corrupting the message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidHeader {
+    /// The name is not an RFC 9110 token.
+    Name(String),
+    /// The value of the named header contains CR, LF, NUL or another control character.
+    Value(String),
+}
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidHeader::Name(name) => write!(f, "invalid header name {:?}", name),
+            InvalidHeader::Value(name) => write!(f, "invalid value for header {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for InvalidHeader {}
+
+/// Whether `value` is an RFC 9110 token, the syntax of header names and methods.
+pub fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Whether `value` can be sent as a field value: no control characters other
+/// than horizontal tab, so no CR, LF or NUL.
+pub fn is_valid_value(value: &str) -> bool {
+    value.bytes().all(|b| b == b'\t' || !b.is_ascii_control())
+}
+
+/// Checks that a header can be written verbatim without splitting the message.
+pub fn validate(header_key: &str, header_value: &str) -> Result<(), InvalidHeader> {
+    if !is_token(header_key.trim()) {
+        return Err(InvalidHeader::Name(header_key.to_string()));
+    }
+    if !is_valid_value(header_value) {
+        return Err(InvalidHeader::Value(header_key.trim().to_string()));
+    }
+    Ok(())
+}
+
+/// An ordered collection of header fields.
+///
+/// Field names are matched case-insensitively, a name may appear more than once,
+/// and fields are written out in the order they were added.
+#[derive(Debug, Clone, Default)]
 pub struct Headers {
-    headers: HashMap<HeaderKey, HeaderValue>,
+    headers: Vec<(HeaderKey, HeaderValue)>,
 }
 
-pub const WWW_AUTHENTICATE: &str = "WWW-Authenticate";
+pub const ACCEPT: &str = "Accept";
+pub const ACCEPT_CHARSET: &str = "Accept-Charset";
+pub const ACCEPT_ENCODING: &str = "Accept-Encoding";
+pub const ACCEPT_LANGUAGE: &str = "Accept-Language";
+pub const ACCEPT_RANGES: &str = "Accept-Ranges";
+pub const ACCESS_CONTROL_ALLOW_CREDENTIALS: &str = "Access-Control-Allow-Credentials";
+pub const ACCESS_CONTROL_ALLOW_HEADERS: &str = "Access-Control-Allow-Headers";
+pub const ACCESS_CONTROL_ALLOW_METHODS: &str = "Access-Control-Allow-Methods";
+pub const ACCESS_CONTROL_ALLOW_ORIGIN: &str = "Access-Control-Allow-Origin";
+pub const ACCESS_CONTROL_EXPOSE_HEADERS: &str = "Access-Control-Expose-Headers";
+pub const ACCESS_CONTROL_MAX_AGE: &str = "Access-Control-Max-Age";
+pub const ACCESS_CONTROL_REQUEST_HEADERS: &str = "Access-Control-Request-Headers";
+pub const ACCESS_CONTROL_REQUEST_METHOD: &str = "Access-Control-Request-Method";
+pub const AGE: &str = "Age";
+pub const ALLOW: &str = "Allow";
+pub const AUTHORIZATION: &str = "Authorization";
+pub const CACHE_CONTROL: &str = "Cache-Control";
+pub const CONNECTION: &str = "Connection";
+pub const CONTENT_DISPOSITION: &str = "Content-Disposition";
+pub const CONTENT_ENCODING: &str = "Content-Encoding";
+pub const CONTENT_LANGUAGE: &str = "Content-Language";
 pub const CONTENT_LENGTH: &str = "Content-Length";
+pub const CONTENT_LOCATION: &str = "Content-Location";
+pub const CONTENT_RANGE: &str = "Content-Range";
+pub const CONTENT_SECURITY_POLICY: &str = "Content-Security-Policy";
+pub const CONTENT_TYPE: &str = "Content-Type";
+pub const COOKIE: &str = "Cookie";
+pub const DATE: &str = "Date";
+pub const ETAG: &str = "ETag";
+pub const EXPECT: &str = "Expect";
+pub const EXPIRES: &str = "Expires";
+pub const FORWARDED: &str = "Forwarded";
+pub const HOST: &str = "Host";
+pub const IF_MATCH: &str = "If-Match";
+pub const IF_MODIFIED_SINCE: &str = "If-Modified-Since";
+pub const IF_NONE_MATCH: &str = "If-None-Match";
+pub const IF_RANGE: &str = "If-Range";
+pub const IF_UNMODIFIED_SINCE: &str = "If-Unmodified-Since";
+pub const KEEP_ALIVE: &str = "Keep-Alive";
+pub const LAST_EVENT_ID: &str = "Last-Event-ID";
+pub const LAST_MODIFIED: &str = "Last-Modified";
+pub const LINK: &str = "Link";
+pub const LOCATION: &str = "Location";
+pub const ORIGIN: &str = "Origin";
+pub const PRAGMA: &str = "Pragma";
+pub const PROXY_AUTHENTICATE: &str = "Proxy-Authenticate";
+pub const PROXY_AUTHORIZATION: &str = "Proxy-Authorization";
+pub const RANGE: &str = "Range";
+pub const REFERER: &str = "Referer";
+pub const RETRY_AFTER: &str = "Retry-After";
+pub const SEC_WEBSOCKET_ACCEPT: &str = "Sec-WebSocket-Accept";
+pub const SEC_WEBSOCKET_EXTENSIONS: &str = "Sec-WebSocket-Extensions";
+pub const SEC_WEBSOCKET_KEY: &str = "Sec-WebSocket-Key";
+pub const SEC_WEBSOCKET_PROTOCOL: &str = "Sec-WebSocket-Protocol";
+pub const SEC_WEBSOCKET_VERSION: &str = "Sec-WebSocket-Version";
 pub const SERVER: &str = "Server";
-
+pub const SET_COOKIE: &str = "Set-Cookie";
+pub const STRICT_TRANSPORT_SECURITY: &str = "Strict-Transport-Security";
+pub const TE: &str = "TE";
+pub const TRAILER: &str = "Trailer";
+pub const TRANSFER_ENCODING: &str = "Transfer-Encoding";
+pub const UPGRADE: &str = "Upgrade";
+pub const USER_AGENT: &str = "User-Agent";
+pub const VARY: &str = "Vary";
+pub const VIA: &str = "Via";
+pub const WWW_AUTHENTICATE: &str = "WWW-Authenticate";
+pub const X_CONTENT_TYPE_OPTIONS: &str = "X-Content-Type-Options";
+pub const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+pub const X_FRAME_OPTIONS: &str = "X-Frame-Options";
 
 impl Headers {
     pub fn new() -> Headers {
         Headers {
-            headers: HashMap::new()
-         }
+            headers: Vec::new(),
+        }
     }
+
     pub fn parse_and_add_header_from(&mut self, header_field: String) -> Result<(), ParseError> {
         match header_field.split_once(':') {
             Some((header_key, header_value)) => {
-                self.add_header(header_key, header_value);
+                self.append(header_key, header_value);
                 Ok(())
             }
-            None => Err(ParseError)
+            None => Err(ParseError("missing colon in header field")),
+        }
+    }
+
+    /// Parses a field line per RFC 9112, section 5: the name must be a token
+    /// immediately followed by the colon, and the value may not contain
+    /// control characters. The line must not include its CRLF.
+    pub fn parse_and_add_header_strict(&mut self, field_line: &str) -> Result<(), ParseError> {
+        let (header_key, header_value) = field_line.split_once(':').ok_or(ParseError("missing colon in header field"))?;
+        let header_value = header_value.trim_matches(|c| c == ' ' || c == '\t');
+        if !is_token(header_key) || !is_valid_value(header_value) {
+            return Err(ParseError("invalid header field"));
         }
+        self.append(header_key, header_value);
+        Ok(())
     }
 
+    /// Returns the first value of `header`.
+    pub fn get(&self, header: &str) -> Option<&String> {
+        self.headers
+            .iter()
+            .find(|(header_key, _)| header_key.eq_ignore_ascii_case(header))
+            .map(|(_, header_value)| header_value)
+    }
+
+    /// Returns every value of `header` in the order they were added.
+    pub fn get_all<'a>(&'a self, header: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(header_key, _)| header_key.eq_ignore_ascii_case(header))
+            .map(|(_, header_value)| header_value)
+    }
+
+    pub fn contains(&self, header: &str) -> bool {
+        self.get(header).is_some()
+    }
+
+    /// Parses `H` from every value of its header, returning `None` if the
+    /// header is absent or malformed.
+    pub fn typed<H: TypedHeader>(&self) -> Option<H> {
+        let values: Vec<&str> = self.get_all(H::name()).map(String::as_str).collect();
+        if values.is_empty() {
+            return None;
+        }
+        H::parse(&values.join(", ")).ok()
+    }
+
+    /// Sets the header of `H` to the formatted value of `header`.
+    pub fn insert_typed<H: TypedHeader>(&mut self, header: &H) {
+        self.insert(H::name(), &header.format());
+    }
+
+    /// Like [`Headers::append`], but rejects fields that fail [`validate`].
+    pub fn try_append(&mut self, header_key: &str, header_value: &str) -> Result<(), InvalidHeader> {
+        validate(header_key, header_value)?;
+        self.append(header_key, header_value);
+        Ok(())
+    }
+
+    /// Like [`Headers::insert`], but rejects fields that fail [`validate`].
+    pub fn try_insert(&mut self, header_key: &str, header_value: &str) -> Result<(), InvalidHeader> {
+        validate(header_key, header_value)?;
+        self.insert(header_key, header_value);
+        Ok(())
+    }
+
+    /// Adds a value for `header`, keeping any values already present.
+    pub fn append(&mut self, header_key: &str, header_value: &str) {
+        self.headers
+            .push((header_key.trim().to_string(), header_value.trim().to_string()));
+    }
+
+    /// Sets `header` to a single value, replacing any values already present.
+    ///
+    /// The replaced field keeps its position in the output.
+    pub fn insert(&mut self, header_key: &str, header_value: &str) {
+        let header_key = header_key.trim();
+        match self
+            .headers
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(header_key))
+        {
+            Some(index) => {
+                self.headers[index].1 = header_value.trim().to_string();
+                let mut position = 0;
+                self.headers.retain(|(key, _)| {
+                    let keep = position <= index || !key.eq_ignore_ascii_case(header_key);
+                    position += 1;
+                    keep
+                });
+            }
+            None => self.append(header_key, header_value),
+        }
+    }
+
+    /// Removes every value of `header`, returning them in order.
+    pub fn remove(&mut self, header: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.headers.retain(|(header_key, header_value)| {
+            if header_key.eq_ignore_ascii_case(header) {
+                removed.push(header_value.clone());
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+
+    #[deprecated(note = "use `Headers::get`")]
     pub fn get_header(&self, header: &str) -> Option<&String> {
-        self.headers.get(header)
+        self.get(header)
     }
 
+    /// Sets `header_key` to `header_value`, replacing any existing values.
+    #[deprecated(note = "use `Headers::insert`, or `Headers::append` to keep existing values")]
     pub fn add_header(&mut self, header_key: &str, header_value: &str) {
-        self.headers.insert(header_key.trim().to_string(), header_value.trim().to_string());
+        self.insert(header_key, header_value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(header_key, header_value)| (header_key.as_str(), header_value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
     }
 }
 
 impl fmt::Display for Headers {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (_size, (header_key, header_value)) in self.headers.iter().enumerate() {
+        for (header_key, header_value) in &self.headers {
             write!(f, "{}: {}{}", header_key, header_value, CRLF)?
         }
 
-        write!(f, "")
+        Ok(())
     }
 }
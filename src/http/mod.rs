@@ -1,12 +1,33 @@
 pub mod request;
+pub mod sse;
+pub mod websocket;
 pub mod response;
+pub mod sendfile;
 pub mod auth;
+pub mod autoindex;
+pub mod body;
+pub mod caching;
+pub mod compression;
+pub mod conditional;
 pub mod errors;
-mod headers;
+pub mod headers;
+pub mod mime;
+pub mod ranges;
+pub mod typed_headers;
 
 pub type HttpVerion = String;
-pub type Body = String;
+pub type Body = Vec<u8>;
 
 const CRLF: &str = "\r\n";
 
-pub struct ParseError;
\ No newline at end of file
+/// A malformed message or header value, with a short description of what was wrong.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseError(pub &'static str);
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
\ No newline at end of file
@@ -0,0 +1,91 @@
+use std::{
+    io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{stream, StreamExt};
+
+use crate::file_source::SourceFile;
+
+use super::{
+    body::{BodyStream, ResponseBody},
+    typed_headers::{ContentRange, Range, TypedHeader},
+    CRLF,
+};
+
+/// Requests for more ranges than this are answered with the whole
+/// representation, so a client can't make the server send many tiny parts.
+pub const MAX_RANGES: usize = 32;
+
+/// Resolves `range` against a representation of `length` bytes, dropping
+/// unsatisfiable ranges and merging ranges that overlap or touch. Returns
+/// `None` if there are too many ranges to honour.
+pub fn resolve(range: &Range, length: u64) -> Option<Vec<(u64, u64)>> {
+    if range.ranges().len() > MAX_RANGES {
+        return None;
+    }
+    let mut resolved: Vec<(u64, u64)> = range.ranges().iter().filter_map(|range| range.resolve(length)).collect();
+    resolved.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::new();
+    for (first, last) in resolved {
+        match merged.last_mut() {
+            Some((_, merged_last)) if first <= merged_last.saturating_add(1) => *merged_last = (*merged_last).max(last),
+            _ => merged.push((first, last)),
+        }
+    }
+    Some(merged)
+}
+
+/// A boundary that is unique per response, so it won't appear in the parts.
+fn boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    format!("yaw-{:08x}{:08x}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Builds a `multipart/byteranges` body holding `ranges` of `file`, which is
+/// `length` bytes of `content_type`. Returns the body, whose length is known
+/// up front, and the `Content-Type` to send it with.
+pub fn multipart_body(
+    file: &SourceFile,
+    ranges: &[(u64, u64)],
+    length: u64,
+    content_type: &str,
+) -> io::Result<(ResponseBody, String)> {
+    let boundary = boundary();
+    let mut parts: Vec<BodyStream> = Vec::new();
+    let mut total = 0;
+    for &(first, last) in ranges {
+        let content_range = ContentRange::Bytes {
+            first,
+            last,
+            length: Some(length),
+        };
+        let head = format!(
+            "{}--{}{}Content-Type: {}{}Content-Range: {}{}{}",
+            CRLF,
+            boundary,
+            CRLF,
+            content_type,
+            CRLF,
+            content_range.format(),
+            CRLF,
+            CRLF
+        );
+        total += head.len() as u64 + last - first + 1;
+        parts.push(ResponseBody::Full(head.into_bytes()).into_stream());
+        // File clones share a cursor, but each part seeks to its offset before reading.
+        parts.push(file.body(first, last - first + 1)?.into_stream());
+    }
+    let tail = format!("{}--{}--{}", CRLF, boundary, CRLF);
+    total += tail.len() as u64;
+    parts.push(ResponseBody::Full(tail.into_bytes()).into_stream());
+    let body = ResponseBody::Stream {
+        stream: Box::pin(stream::iter(parts).flatten()),
+        length: Some(total),
+    };
+    Ok((body, format!("multipart/byteranges; boundary={}", boundary)))
+}
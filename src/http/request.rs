@@ -1,30 +1,34 @@
 use super::{
-    errors::{HttpError, ServerError},
-    headers::Headers,
-    Body, HttpVerion, ParseError, CRLF,
+    errors::{ClientError, HttpError, ServerError},
+    headers::{self, Headers},
+    typed_headers::{Host, TypedHeader},
+    Body, HttpVerion, ParseError,
 };
 
 use std::{
     collections::HashMap,
+    io,
 };
-use tokio::{net::TcpStream, io::{BufReader, AsyncBufReadExt}};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
 
 type _URI = String;
 type Params = HashMap<String, String>;
 
+#[derive(Clone)]
 pub struct Request {
     request_line: RequestLine,
     headers: Headers,
-    _body: Body,
+    body: Body,
 }
 
+#[derive(Clone)]
 pub struct RequestLine {
     pub method: Method,
     pub request_target: RequestTarget,
     pub http_version: HttpVerion,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum Method {
     GET,
     POST,
@@ -33,35 +37,292 @@ pub enum Method {
     HEAD,
 }
 
+#[derive(Clone)]
 pub struct RequestTarget {
     absolute_path: String,
     query: Option<Query>,
 }
 
+#[derive(Clone)]
 pub struct Query {
+    query: String,
     params: Params,
 }
 
+/// Bounds on the size of a request head, and whether it is parsed strictly.
+///
+/// Lengths exclude the line terminator. A request line over the limit is
+/// answered with 414; oversized header fields, too many fields or an oversized
+/// head as a whole are answered with 431.
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    /// Parse the head per RFC 9112: single spaces in the request line, a valid
+    /// HTTP version, CRLF line endings, no whitespace before the colon of a
+    /// field and exactly one valid `Host` for HTTP/1.1.
+    ///
+    /// Off by default, as it turns away clients that end lines with a bare LF
+    /// or leave out `Host`.
+    pub strict: bool,
+    pub max_request_line_length: usize,
+    pub max_header_length: usize,
+    pub max_header_count: usize,
+    pub max_head_size: usize,
+    /// Larger bodies are answered with 413.
+    pub max_body_size: u64,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            strict: false,
+            max_request_line_length: 8 * 1024,
+            max_header_length: 8 * 1024,
+            max_header_count: 100,
+            max_head_size: 64 * 1024,
+            max_body_size: 1024 * 1024,
+        }
+    }
+}
+
+enum Line {
+    Complete(Vec<u8>),
+    TooLong,
+    Incomplete,
+}
+
+/// Reads up to and including the next LF, giving up once more than
+/// `max_length` bytes (plus CRLF) have been buffered.
+async fn read_line_limited<R: AsyncBufRead + Unpin>(reader: &mut R, max_length: usize) -> io::Result<Line> {
+    let mut line = Vec::new();
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(Line::Incomplete);
+        }
+        match available.iter().position(|&b| b == b'\n') {
+            Some(index) => {
+                line.extend_from_slice(&available[..=index]);
+                reader.consume(index + 1);
+                if line.len() > max_length + 2 {
+                    return Ok(Line::TooLong);
+                }
+                return Ok(Line::Complete(line));
+            }
+            None => {
+                let length = available.len();
+                line.extend_from_slice(available);
+                reader.consume(length);
+                if line.len() > max_length + 2 {
+                    return Ok(Line::TooLong);
+                }
+            }
+        }
+    }
+}
+
+/// Removes the line terminator. Strict parsing requires CRLF and rejects a
+/// bare CR anywhere in the line; otherwise a bare LF is accepted too.
+fn strip_line_ending(mut line: Vec<u8>, strict: bool) -> Result<String, ParseError> {
+    line.pop();
+    let had_cr = line.last() == Some(&b'\r');
+    if had_cr {
+        line.pop();
+    }
+    if strict && (!had_cr || line.contains(&b'\r')) {
+        return Err(ParseError("bare CR or missing CRLF"));
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// How the length of a request body is determined, per RFC 9112, section 6.3.
+enum Framing {
+    None,
+    ContentLength(u64),
+    Chunked,
+}
+
+/// Works out the body framing, rejecting anything a proxy in front of us could
+/// interpret differently: `Content-Length` together with `Transfer-Encoding`,
+/// repeated or malformed `Content-Length` values, and transfer codings that
+/// do not end in `chunked`.
+fn framing(headers: &Headers, http_version: &str) -> Result<Framing, HttpError> {
+    let transfer_encodings: Vec<&String> = headers.get_all(headers::TRANSFER_ENCODING).collect();
+    let content_lengths: Vec<&str> = headers
+        .get_all(headers::CONTENT_LENGTH)
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    if !transfer_encodings.is_empty() {
+        if !content_lengths.is_empty() || http_version == "HTTP/1.0" {
+            return Err(ParseError("Transfer-Encoding with Content-Length or in HTTP/1.0").into());
+        }
+        let codings: Vec<String> = transfer_encodings
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|coding| coding.trim().to_ascii_lowercase())
+            .collect();
+        return match codings.as_slice() {
+            [coding] if coding == "chunked" => Ok(Framing::Chunked),
+            [.., last] if last == "chunked" => Err(HttpError::from(ServerError::MethodNotImplemented)),
+            _ => Err(ParseError("unsupported Transfer-Encoding").into()),
+        };
+    }
+
+    match content_lengths.as_slice() {
+        [] => Ok(Framing::None),
+        [content_length] => {
+            if content_length.is_empty() || !content_length.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseError("invalid Content-Length").into());
+            }
+            content_length
+                .parse()
+                .map(Framing::ContentLength)
+                .map_err(|_| HttpError::from(ClientError::ContentTooLarge))
+        }
+        _ => Err(ParseError("multiple Content-Length values").into()),
+    }
+}
+
+/// Parses a `chunk-size [ chunk-ext ]` line, without its CRLF.
+fn parse_chunk_size(line: &str) -> Result<u64, ParseError> {
+    let (chunk_size, chunk_ext) = match line.split_once(';') {
+        Some((chunk_size, chunk_ext)) => (chunk_size.trim_end_matches([' ', '\t']), Some(chunk_ext)),
+        None => (line, None),
+    };
+    if chunk_size.is_empty() || !chunk_size.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ParseError("invalid chunk size"));
+    }
+    if chunk_ext.is_some_and(|chunk_ext| !headers::is_valid_value(chunk_ext)) {
+        return Err(ParseError("invalid chunk extension"));
+    }
+    u64::from_str_radix(chunk_size, 16).map_err(|_| ParseError("invalid chunk size"))
+}
+
+async fn read_exact_body<R: AsyncBufRead + Unpin>(reader: &mut R, body: &mut Vec<u8>, length: u64) -> Result<(), HttpError> {
+    let start = body.len();
+    body.resize(start + length as usize, 0);
+    reader
+        .read_exact(&mut body[start..])
+        .await
+        .map_err(|_| HttpError::from(ParseError("body shorter than declared")))?;
+    Ok(())
+}
+
+/// Reads a chunked body, always requiring CRLF after chunk lines and data.
+/// Trailer fields are validated against the header limits and discarded.
+async fn read_chunked_body<R: AsyncBufRead + Unpin>(reader: &mut R, limits: &RequestLimits) -> Result<Vec<u8>, HttpError> {
+    let mut body = Vec::new();
+    loop {
+        let line = match read_line_limited(reader, limits.max_header_length).await {
+            Ok(Line::Complete(line)) => strip_line_ending(line, true)?,
+            Ok(Line::TooLong) | Ok(Line::Incomplete) => return Err(ParseError("invalid chunk size line").into()),
+            Err(_e) => return Err(HttpError::from(ServerError::InternalServerError)),
+        };
+        let chunk_size = parse_chunk_size(&line)?;
+        if chunk_size == 0 {
+            break;
+        }
+        if chunk_size > limits.max_body_size - body.len() as u64 {
+            return Err(HttpError::from(ClientError::ContentTooLarge));
+        }
+        read_exact_body(reader, &mut body, chunk_size).await?;
+        let mut crlf = [0; 2];
+        match reader.read_exact(&mut crlf).await {
+            Ok(_) if &crlf == b"\r\n" => {}
+            _ => return Err(ParseError("missing CRLF after chunk data").into()),
+        }
+    }
+
+    let mut trailers = Headers::new();
+    loop {
+        let line = match read_line_limited(reader, limits.max_header_length).await {
+            Ok(Line::Complete(line)) => strip_line_ending(line, true)?,
+            Ok(Line::TooLong) => return Err(HttpError::from(ClientError::RequestHeaderFieldsTooLarge)),
+            Ok(Line::Incomplete) => return Err(ParseError("incomplete trailer section").into()),
+            Err(_e) => return Err(HttpError::from(ServerError::InternalServerError)),
+        };
+        if line.is_empty() {
+            return Ok(body);
+        }
+        if trailers.len() == limits.max_header_count {
+            return Err(HttpError::from(ClientError::RequestHeaderFieldsTooLarge));
+        }
+        trailers.parse_and_add_header_strict(&line)?;
+    }
+}
+
+fn is_valid_http_version(http_version: &str) -> bool {
+    match http_version.strip_prefix("HTTP/").map(str::as_bytes) {
+        Some([major, b'.', minor]) => major.is_ascii_digit() && minor.is_ascii_digit(),
+        _ => false,
+    }
+}
+
 impl RequestLine {
     pub fn parse(request_line: String) -> Result<RequestLine, ParseError> {
         let mut request_line_items = request_line.split_ascii_whitespace().map(|s| s.to_string());
         let method = match request_line_items.next() {
             Some(method_string) => Method::parse(&method_string)?,
-            None => return Err(ParseError),
+            None => return Err(ParseError("missing method")),
         };
         let request_target = match request_line_items.next() {
             Some(request_target_string) => RequestTarget::parse(request_target_string)?,
-            None => return Err(ParseError),
+            None => return Err(ParseError("missing request target")),
         };
         let http_version = match request_line_items.next() {
             Some(http_version_string) => http_version_string,
-            None => return Err(ParseError),
+            None => return Err(ParseError("missing HTTP version")),
         };
 
         Ok(RequestLine {
-            method: method,
-            request_target: request_target,
-            http_version: http_version,
+            method,
+            request_target,
+            http_version,
+        })
+    }
+
+    /// Parses a request line per RFC 9112, section 3: exactly
+    /// `method SP request-target SP HTTP-version`, without the CRLF.
+    ///
+    /// Absolute-form targets are reduced to their path and query.
+    pub fn parse_strict(request_line: &str) -> Result<RequestLine, ParseError> {
+        let mut request_line_items = request_line.split(' ');
+        let (method, request_target, http_version) = match (
+            request_line_items.next(),
+            request_line_items.next(),
+            request_line_items.next(),
+            request_line_items.next(),
+        ) {
+            (Some(method), Some(request_target), Some(http_version), None) => {
+                (method, request_target, http_version)
+            }
+            _ => return Err(ParseError("request line must have three space-separated parts")),
+        };
+        if !headers::is_token(method) || !is_valid_http_version(http_version) {
+            return Err(ParseError("invalid method or HTTP version"));
+        }
+        if request_target.is_empty() || !request_target.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(ParseError("invalid request target"));
+        }
+        let request_target = if request_target.starts_with('/') || request_target == "*" {
+            request_target.to_string()
+        } else {
+            let (scheme, rest) = request_target.split_once("://").ok_or(ParseError("unsupported request target form"))?;
+            if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+                return Err(ParseError("unsupported request target form"));
+            }
+            match rest.find(['/', '?']) {
+                Some(index) if rest[index..].starts_with('/') => rest[index..].to_string(),
+                Some(index) => format!("/{}", &rest[index..]),
+                None => "/".to_string(),
+            }
+        };
+
+        Ok(RequestLine {
+            method: Method::parse(method)?,
+            request_target: RequestTarget::parse(request_target)?,
+            http_version: http_version.to_string(),
         })
     }
 
@@ -69,28 +330,82 @@ impl RequestLine {
         &self.method
     }
 
+    pub fn get_http_version(&self) -> &str {
+        &self.http_version
+    }
+
     pub fn get_path(&self) -> &str {
-        &self.request_target.get_path()
+        self.request_target.get_path()
     }
 
     pub fn get_param(&self, param: &str) -> Option<&String> {
         self.request_target.get_param(param)
     }
+
+    pub fn get_query(&self) -> Option<&str> {
+        self.request_target.get_query()
+    }
+}
+
+/// Decodes the percent escapes of a request path.
+///
+/// Escaped slashes, NUL bytes and `..` segments are rejected, as is anything
+/// that doesn't decode to UTF-8, so a decoded path can't name a file outside
+/// the directory it is looked up in.
+fn decode_path(path: &str) -> Result<String, ParseError> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let escape = [bytes.next(), bytes.next()];
+        let byte = match escape {
+            [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match byte {
+            Some(b'/') => return Err(ParseError("escaped slash in path")),
+            Some(byte) => decoded.push(byte),
+            None => return Err(ParseError("invalid percent escape in path")),
+        }
+    }
+    let decoded = String::from_utf8(decoded).map_err(|_| ParseError("path is not UTF-8"))?;
+    if decoded.contains('\0') {
+        return Err(ParseError("NUL in path"));
+    }
+    if decoded.split('/').any(|segment| segment == "..") {
+        return Err(ParseError("dot-dot segment in path"));
+    }
+    Ok(decoded)
+}
+
+/// Percent-encodes `path` for a link or `Location`, keeping its slashes.
+pub(crate) fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
 }
 
 impl RequestTarget {
+    /// Splits off the query and decodes the path; the query is kept as sent.
     pub fn parse(request_target: String) -> Result<RequestTarget, ParseError> {
         let (absolute_path, query_string) = match request_target.split_once('?') {
-            Some((absolute_path, query_string)) => (absolute_path.to_string(), Some(query_string)),
-            None => (request_target, None),
-        };
-        let query = match query_string {
-            Some(query_string) => Some(Query::parse(query_string.to_string())),
-            None => None,
+            Some((absolute_path, query_string)) => (decode_path(absolute_path)?, Some(query_string)),
+            None => (decode_path(&request_target)?, None),
         };
+        let query = query_string.map(|query_string| Query::parse(query_string.to_string()));
         Ok(RequestTarget {
-            absolute_path: absolute_path,
-            query: query,
+            absolute_path,
+            query,
         })
     }
 
@@ -101,6 +416,10 @@ impl RequestTarget {
     pub fn get_param(&self, param: &str) -> Option<&String> {
         self.query.as_ref()?.get_param(param)
     }
+
+    pub fn get_query(&self) -> Option<&str> {
+        self.query.as_ref().map(Query::as_str)
+    }
 }
 
 impl Method {
@@ -111,7 +430,7 @@ impl Method {
             "PUT" => Ok(Method::PUT),
             "DELETE" => Ok(Method::DELETE),
             "HEAD" => Ok(Method::HEAD),
-            _ => Err(ParseError),
+            _ => Err(ParseError("unsupported method")),
         }
     }
 }
@@ -123,60 +442,164 @@ impl Query {
         for param_string in params_strings_list {
             let (param_key, param_value) = param_string
                 .split_once('=')
-                .unwrap_or_else(|| (param_string, ""));
+                .unwrap_or((param_string, ""));
             params.insert(param_key.to_string(), param_value.to_string());
         }
-        Query { params: params }
+        Query { query, params }
     }
 
     pub fn get_param(&self, param: &str) -> Option<&String> {
         self.params.get(param)
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.query
+    }
 }
 
 impl Request {
-    pub async fn load(request_stream: &mut TcpStream) -> Result<Request, HttpError> {
-        let mut buf_reader = BufReader::new(request_stream);
-        let mut request_line = String::new();
-        match buf_reader.read_line(&mut request_line).await {
-            Ok(_) => (),
-            Err(_e) => return Err(HttpError::ServerError(ServerError::InternalServerError)),
+    pub async fn load<R: AsyncRead + Unpin>(request_stream: &mut R) -> Result<Request, HttpError> {
+        Self::load_with_limits(request_stream, &RequestLimits::default()).await
+    }
+
+    /// Reads a request through a buffer of its own, so anything the client
+    /// sent after the request is lost. Use [`Request::load_buffered`] to keep it.
+    pub async fn load_with_limits<R: AsyncRead + Unpin>(
+        request_stream: &mut R,
+        limits: &RequestLimits,
+    ) -> Result<Request, HttpError> {
+        Self::load_buffered(&mut BufReader::new(request_stream), limits).await
+    }
+
+    /// Reads a request from `buf_reader`, leaving whatever follows it, such as
+    /// the first frames after a WebSocket handshake, in its buffer.
+    pub async fn load_buffered<R: AsyncBufRead + Unpin>(
+        buf_reader: &mut R,
+        limits: &RequestLimits,
+    ) -> Result<Request, HttpError> {
+        let mut head_size = 0;
+
+        // RFC 9112 asks servers to ignore at least one empty line before the request line.
+        let mut request_line;
+        let mut empty_lines = 0;
+        loop {
+            request_line = match read_line_limited(buf_reader, limits.max_request_line_length).await {
+                Ok(Line::Complete(line)) => line,
+                Ok(Line::TooLong) => return Err(HttpError::from(ClientError::UriTooLong)),
+                Ok(Line::Incomplete) => return Err(ParseError("incomplete request line").into()),
+                Err(_e) => return Err(HttpError::from(ServerError::InternalServerError)),
+            };
+            head_size += request_line.len();
+            if request_line != b"\r\n" && request_line != b"\n" {
+                break;
+            }
+            empty_lines += 1;
+            if empty_lines > 1 {
+                return Err(ParseError("too many empty lines before request line").into());
+            }
         }
-        let request_line = RequestLine::parse(request_line)?;
+        let request_line = strip_line_ending(request_line, limits.strict)?;
+        let request_line = if limits.strict {
+            RequestLine::parse_strict(&request_line)?
+        } else {
+            RequestLine::parse(request_line)?
+        };
 
         let mut headers = Headers::new();
         loop {
-            let mut line = String::new();
-            match buf_reader.read_line(&mut line).await {
-                Ok(_) => (),
-                Err(_e) => return Err(HttpError::ServerError(ServerError::InternalServerError)),
+            let line = match read_line_limited(buf_reader, limits.max_header_length).await {
+                Ok(Line::Complete(line)) => line,
+                Ok(Line::TooLong) => {
+                    return Err(HttpError::from(ClientError::RequestHeaderFieldsTooLarge))
+                }
+                Ok(Line::Incomplete) => return Err(ParseError("incomplete header section").into()),
+                Err(_e) => return Err(HttpError::from(ServerError::InternalServerError)),
             };
-            if line == CRLF {
+            head_size += line.len();
+            if head_size > limits.max_head_size {
+                return Err(HttpError::from(ClientError::RequestHeaderFieldsTooLarge));
+            }
+            let line = strip_line_ending(line, limits.strict)?;
+            if line.is_empty() {
                 break;
             }
-            headers.parse_and_add_header_from(line)?;
+            // obs-fold continuation lines are rejected in every mode, as
+            // recipients disagree on where the folded field ends.
+            if line.starts_with([' ', '\t']) {
+                return Err(ParseError("obs-fold line in header section").into());
+            }
+            if headers.len() == limits.max_header_count {
+                return Err(HttpError::from(ClientError::RequestHeaderFieldsTooLarge));
+            }
+            if limits.strict {
+                headers.parse_and_add_header_strict(&line)?;
+            } else {
+                headers.parse_and_add_header_from(line)?;
+            }
+        }
+
+        if limits.strict && request_line.get_http_version() == "HTTP/1.1" {
+            let hosts: Vec<&String> = headers.get_all(headers::HOST).collect();
+            if hosts.len() != 1 || (!hosts[0].is_empty() && Host::parse(hosts[0]).is_err()) {
+                return Err(ParseError("missing, repeated or invalid Host header").into());
+            }
         }
 
+        let body = match framing(&headers, request_line.get_http_version())? {
+            Framing::None => Vec::new(),
+            Framing::ContentLength(length) if length > limits.max_body_size => {
+                return Err(HttpError::from(ClientError::ContentTooLarge))
+            }
+            Framing::ContentLength(length) => {
+                let mut body = Vec::new();
+                read_exact_body(buf_reader, &mut body, length).await?;
+                body
+            }
+            Framing::Chunked => read_chunked_body(buf_reader, limits).await?,
+        };
+
         Ok(Request {
-            request_line: request_line,
-            headers: headers,
-            _body: "".to_string(),
+            request_line,
+            headers,
+            body,
         })
     }
 
     pub fn get_path(&self) -> &str {
-        &self.request_line.get_path()
+        self.request_line.get_path()
     }
 
     pub fn get_method(&self) -> &Method {
-        &self.request_line.get_method()
+        self.request_line.get_method()
+    }
+
+    pub fn get_http_version(&self) -> &str {
+        self.request_line.get_http_version()
     }
 
     pub fn get_header(&self, header: &str) -> Option<&String> {
-        self.headers.get_header(header)
+        self.headers.get(header)
+    }
+
+    /// Parses a typed header, e.g. `request.typed_header::<Accept>()`.
+    pub fn typed_header<H: TypedHeader>(&self) -> Option<H> {
+        self.headers.typed()
+    }
+
+    pub fn get_headers(&self) -> &Headers {
+        &self.headers
     }
 
     pub fn get_param(&self, param: &str) -> Option<&String> {
         self.request_line.get_param(param)
     }
+
+    /// The query string without the `?`, if the target had one.
+    pub fn get_query(&self) -> Option<&str> {
+        self.request_line.get_query()
+    }
+
+    pub fn get_body(&self) -> &[u8] {
+        &self.body
+    }
 }
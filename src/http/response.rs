@@ -1,41 +1,161 @@
-use tokio::net::TcpStream;
-
 use crate::http::*;
-use std::fmt;
+use std::{fmt, io};
 
-use super::headers::Headers;
-use tokio::io::AsyncWriteExt;
+use super::{
+    body::{BodyStream, ResponseBody},
+    errors::HttpError,
+    headers::{Headers, InvalidHeader},
+    typed_headers::TypedHeader,
+};
+use futures::Stream;
+use serde::Serialize;
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt, BufWriter},
+    net::TcpStream,
+};
 
 const SERVER_NAME: &str = "yaw";
 
-#[derive(Debug, PartialEq)]
-pub enum Status {
-    Ok,
-    Found,
-    NotFound,
-    NotImplemented,
-    InvalidRequest,
-    Unauthorized,
-    MethodNotAllowed,
-    InternalServerError,
-    BadGateway,
-    ServiceUnavailable,
+/// Defines `Status` from the IANA HTTP Status Code Registry, keeping each
+/// variant, its code and its reason phrase on one line.
+macro_rules! statuses {
+    ($($variant:ident = $code:literal, $reason_phrase:literal;)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Status {
+            $($variant,)*
+            /// Any other three-digit code, sent without a reason phrase.
+            Custom(CustomCode),
+        }
+
+        impl Status {
+            /// Returns the status for `code`, or `None` if it is not a three-digit code.
+            pub fn from_u16(code: u16) -> Option<Status> {
+                match code {
+                    $($code => Some(Status::$variant),)*
+                    100..=999 => Some(Status::Custom(CustomCode(code))),
+                    _ => None,
+                }
+            }
+
+            pub fn as_u16(&self) -> u16 {
+                match self {
+                    $(Status::$variant => $code,)*
+                    Status::Custom(code) => code.get(),
+                }
+            }
+
+            pub fn reason_phrase(&self) -> &'static str {
+                match self {
+                    $(Status::$variant => $reason_phrase,)*
+                    Status::Custom(_) => "",
+                }
+            }
+        }
+    };
+}
+
+/// A three-digit status code missing from the registry. Only
+/// [`Status::from_u16`] makes these, so registered codes always get their
+/// own variant and compare equal to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomCode(u16);
+
+impl CustomCode {
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+statuses! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableContent = 422, "Unprocessable Content";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
 }
 
 impl Status {
-    fn get_reason_phrase(&self) -> String{
-        match self {
-            Status::Ok => "Ok".to_string(),
-            Status::Found => "Found".to_string(),
-            Status::NotFound => "Not Found".to_string(),
-            Status::NotImplemented => "Not Implemented".to_string(),
-            Status::InvalidRequest => "Invalid Request".to_string(),
-            Status::Unauthorized => "Unauthorized".to_string(),
-            Status::MethodNotAllowed => "Method Not Allowed".to_string(),
-            Status::InternalServerError => "Internal Server Error".to_string(),
-            Status::BadGateway => "Bad Gateway".to_string(),
-            Status::ServiceUnavailable => "Service Unavailable".to_string(),
-        }
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.as_u16(), self.reason_phrase())
     }
 }
 
@@ -50,12 +170,12 @@ impl StatusLine {
         StatusLine {
             http_version: "HTTP/1.1".to_string(),
             status_code: Status::Ok,
-            reason_phrase: Status::Ok.get_reason_phrase()
+            reason_phrase: Status::Ok.reason_phrase().to_string(),
         }
     }
 
     pub fn set_status_code(&mut self, status_code: Status) {
-        self.reason_phrase = status_code.get_reason_phrase();
+        self.reason_phrase = status_code.reason_phrase().to_string();
         self.status_code = status_code;
     }
 
@@ -64,75 +184,381 @@ impl StatusLine {
     }
 }
 
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Display for StatusLine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "{} {} {}",
             self.http_version,
-            match self.status_code {
-                Status::Ok => "200",
-                Status::Found => "302",
-                Status::NotFound => "404",
-                Status::NotImplemented => "501",
-                Status::InvalidRequest => "400",
-                Status::Unauthorized => "401",
-                Status::MethodNotAllowed => "405",
-                Status::InternalServerError => "500",
-                Status::BadGateway => "502",
-                Status::ServiceUnavailable => "503",
-            }.to_string(), 
+            self.status_code.as_u16(),
             self.reason_phrase
         )
     }
 }
 
+/// The redirect statuses, for [`Response::redirect`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Redirect {
+    /// 301, may change the method to GET.
+    MovedPermanently,
+    /// 302, may change the method to GET.
+    Found,
+    /// 303, always followed with GET.
+    SeeOther,
+    /// 307, keeps the method and body.
+    TemporaryRedirect,
+    /// 308, keeps the method and body.
+    PermanentRedirect,
+}
+
+impl Redirect {
+    pub fn status(&self) -> Status {
+        match self {
+            Redirect::MovedPermanently => Status::MovedPermanently,
+            Redirect::Found => Status::Found,
+            Redirect::SeeOther => Status::SeeOther,
+            Redirect::TemporaryRedirect => Status::TemporaryRedirect,
+            Redirect::PermanentRedirect => Status::PermanentRedirect,
+        }
+    }
+}
+
+/// Takes over the connection after a `101 Switching Protocols` response,
+/// along with any bytes the client sent after the request that have already
+/// been read from it.
+pub type Upgrade = Box<dyn FnOnce(TcpStream, Vec<u8>) + Send>;
+
 pub struct Response {
     status_line: StatusLine,
     headers: Headers,
-    body: String,
+    body: ResponseBody,
+    upgrade: Option<Upgrade>,
 }
 
 impl Response {
     pub fn new() -> Response {
         let mut headers = Headers::new();
-        headers.add_header(headers::SERVER, SERVER_NAME);
-        headers.add_header(headers::CONTENT_LENGTH, "0");
+        headers.insert(headers::SERVER, SERVER_NAME);
+        headers.insert(headers::CONTENT_LENGTH, "0");
         Response {
             status_line: StatusLine::new(),
-            headers: headers,
-            body: "".to_string(),
+            headers,
+            body: ResponseBody::empty(),
+            upgrade: None,
         }
     }
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// A 200 response with `value` serialized as `application/json`.
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Result<Response, HttpError> {
+        Response::builder().json(value).build()
+    }
+
+    /// A 200 response with a `text/html; charset=utf-8` body.
+    pub fn html(content: impl Into<String>) -> Response {
+        let mut response = Response::new();
+        response.add_header(headers::CONTENT_TYPE, "text/html; charset=utf-8");
+        response.set_body(content.into());
+        response
+    }
+
+    /// A 200 response with a `text/plain; charset=utf-8` body.
+    pub fn text(content: impl Into<String>) -> Response {
+        let mut response = Response::new();
+        response.add_header(headers::CONTENT_TYPE, "text/plain; charset=utf-8");
+        response.set_body(content.into());
+        response
+    }
+
+    /// A redirect to `location`. Fails if `location` could not be sent as a
+    /// header value, e.g. because it contains CR or LF.
+    pub fn redirect(location: &str, kind: Redirect) -> Result<Response, HttpError> {
+        Response::builder()
+            .status(kind.status())
+            .header(headers::LOCATION, location)
+            .build()
+    }
+
+    /// A 204 response, which has neither a body nor `Content-Length`.
+    pub fn no_content() -> Response {
+        let mut response = Response::new();
+        response.set_status_code(Status::NoContent);
+        response.remove_header(headers::CONTENT_LENGTH);
+        response
+    }
+
     pub fn set_status_code(&mut self, status_code: Status) {
         self.status_line.set_status_code(status_code);
     }
 
+    /// Sets `header_key` to `header_value`, replacing any existing values.
+    ///
+    /// A header that would corrupt the response, such as a value containing
+    /// CR or LF, is logged and dropped. Use [`Response::try_add_header`] to
+    /// handle the error instead.
     pub fn add_header(&mut self, header_key: &str, header_value: &str) {
-        self.headers.add_header(header_key, header_value);
+        if let Err(e) = self.try_add_header(header_key, header_value) {
+            eprintln!("dropping response header: {}", e);
+        }
+    }
+
+    /// Adds another value for `header_key`, keeping any existing values.
+    ///
+    /// Invalid headers are logged and dropped, as with [`Response::add_header`].
+    pub fn append_header(&mut self, header_key: &str, header_value: &str) {
+        if let Err(e) = self.try_append_header(header_key, header_value) {
+            eprintln!("dropping response header: {}", e);
+        }
+    }
+
+    pub fn try_add_header(&mut self, header_key: &str, header_value: &str) -> Result<(), InvalidHeader> {
+        self.headers.try_insert(header_key, header_value)
+    }
+
+    pub fn try_append_header(&mut self, header_key: &str, header_value: &str) -> Result<(), InvalidHeader> {
+        self.headers.try_append(header_key, header_value)
+    }
+
+    pub fn set_typed_header<H: TypedHeader>(&mut self, header: &H) {
+        self.add_header(H::name(), &header.format());
+    }
+
+    pub fn remove_header(&mut self, header_key: &str) -> Vec<String> {
+        self.headers.remove(header_key)
+    }
+
+    pub fn get_header(&self, header_key: &str) -> Option<&String> {
+        self.headers.get(header_key)
+    }
+
+    pub fn get_headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Parses a header into its typed form, or `None` if absent or invalid.
+    pub fn typed_header<H: TypedHeader>(&self) -> Option<H> {
+        self.headers.typed()
     }
 
     pub fn set_body(&mut self, content: String) {
-        let length = content.len();
-        self.add_header(headers::CONTENT_LENGTH, &length.to_string());
-        self.body = content;
+        self.set_body_bytes(content.into_bytes());
+    }
+
+    pub fn set_body_bytes(&mut self, content: Vec<u8>) {
+        self.set_response_body(ResponseBody::Full(content));
+    }
+
+    /// Streams `length` bytes of `file` from its start, without reading it into memory.
+    pub fn set_body_file(&mut self, file: File, length: u64) {
+        self.set_response_body(ResponseBody::File {
+            file,
+            offset: 0,
+            length,
+        });
+    }
+
+    /// Streams chunks as they are produced. The body is sent with chunked
+    /// `Transfer-Encoding`, or delimited by closing the connection for
+    /// HTTP/1.0 clients.
+    pub fn set_body_stream<S>(&mut self, stream: S)
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        let stream: BodyStream = Box::pin(stream);
+        self.set_response_body(ResponseBody::Stream { stream, length: None });
+    }
+
+    /// Replaces the body, updating `Content-Length` to match.
+    pub fn set_response_body(&mut self, body: ResponseBody) {
+        match body.len() {
+            Some(length) => self.add_header(headers::CONTENT_LENGTH, &length.to_string()),
+            None => {
+                self.remove_header(headers::CONTENT_LENGTH);
+            }
+        }
+        self.body = body;
+    }
+
+    pub fn get_body(&self) -> &ResponseBody {
+        &self.body
+    }
+
+    /// Takes the body out, leaving an empty one and the headers unchanged.
+    pub fn take_body(&mut self) -> ResponseBody {
+        std::mem::take(&mut self.body)
     }
 
     pub fn get_status_code(&self) -> &Status{
-        &self.status_line.get_status_code()
+        self.status_line.get_status_code()
+    }
+
+    /// Hands the connection to `upgrade` once this response has been sent,
+    /// instead of closing it. Only used for `101 Switching Protocols`.
+    pub fn on_upgrade<F>(&mut self, upgrade: F)
+    where
+        F: FnOnce(TcpStream, Vec<u8>) + Send + 'static,
+    {
+        self.upgrade = Some(Box::new(upgrade));
+    }
+
+    pub fn take_upgrade(&mut self) -> Option<Upgrade> {
+        self.upgrade.take()
+    }
+
+    /// Writes the response to `stream`.
+    ///
+    /// `client_version` is the HTTP version of the request. A body of unknown
+    /// length is sent chunked to HTTP/1.1 clients; HTTP/1.0 clients cannot
+    /// parse chunks, so the connection is closed after the body instead.
+    pub async fn send_to<W: AsyncWrite + Unpin>(mut self, stream: &mut W, client_version: &str) -> io::Result<()> {
+        let chunked = self.body.len().is_none() && client_version != "HTTP/1.0";
+        if self.body.len().is_none() {
+            self.remove_header(headers::CONTENT_LENGTH);
+            if chunked {
+                self.add_header(headers::TRANSFER_ENCODING, "chunked");
+            } else {
+                self.add_header(headers::CONNECTION, "close");
+            }
+        }
+        let mut writer = BufWriter::new(stream);
+        writer.write_all(self.head().as_bytes()).await?;
+        let body = self.take_body();
+        body.write_to(&mut writer, chunked).await?;
+        writer.flush().await
+    }
+
+    /// Like [`Response::send_to`], but large file bodies are sent with
+    /// `sendfile(2)` on Linux instead of being copied through a buffer.
+    pub async fn send_to_tcp(mut self, stream: &mut TcpStream, client_version: &str) -> io::Result<()> {
+        let (file, offset, length) = match self.take_body() {
+            ResponseBody::File { file, offset, length } if length >= sendfile::MIN_SIZE => (file, offset, length),
+            body => {
+                self.set_response_body(body);
+                return self.send_to(stream, client_version).await;
+            }
+        };
+        stream.write_all(self.head().as_bytes()).await?;
+        if sendfile::send_file(stream, &file, offset, length).await? {
+            return Ok(());
+        }
+        let mut writer = BufWriter::new(stream);
+        ResponseBody::File { file, offset, length }.write_to(&mut writer, false).await?;
+        writer.flush().await
+    }
+
+    /// The status line and header section, including the final empty line.
+    pub fn head(&self) -> String {
+        format!("{}{}{}{}", self.status_line, CRLF, self.headers, CRLF)
+    }
+}
+
+/// Builds a [`Response`] step by step. The first invalid header or body
+/// error is kept and returned by [`ResponseBuilder::build`].
+pub struct ResponseBuilder {
+    response: Response,
+    error: Option<HttpError>,
+}
+
+impl ResponseBuilder {
+    pub fn new() -> ResponseBuilder {
+        ResponseBuilder {
+            response: Response::new(),
+            error: None,
+        }
+    }
+
+    pub fn status(mut self, status_code: Status) -> ResponseBuilder {
+        self.response.set_status_code(status_code);
+        self
+    }
+
+    /// Sets a header, replacing any existing values.
+    pub fn header(mut self, header_key: &str, header_value: &str) -> ResponseBuilder {
+        if let Err(e) = self.response.try_add_header(header_key, header_value) {
+            self.error.get_or_insert(e.into());
+        }
+        self
+    }
+
+    /// Adds another value for a header.
+    pub fn append_header(mut self, header_key: &str, header_value: &str) -> ResponseBuilder {
+        if let Err(e) = self.response.try_append_header(header_key, header_value) {
+            self.error.get_or_insert(e.into());
+        }
+        self
+    }
+
+    pub fn typed_header<H: TypedHeader>(self, header: &H) -> ResponseBuilder {
+        self.header(H::name(), &header.format())
+    }
+
+    pub fn body(mut self, content: impl Into<String>) -> ResponseBuilder {
+        self.response.set_body(content.into());
+        self
+    }
+
+    pub fn body_stream<S>(mut self, stream: S) -> ResponseBuilder
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        self.response.set_body_stream(stream);
+        self
+    }
+
+    pub fn html(self, content: impl Into<String>) -> ResponseBuilder {
+        self.header(headers::CONTENT_TYPE, "text/html; charset=utf-8").body(content)
     }
 
-    pub async fn send_to(&self, mut stream: TcpStream) -> Result<usize, std::io::Error> {
-        stream.write(self.to_string().as_bytes()).await
+    pub fn text(self, content: impl Into<String>) -> ResponseBuilder {
+        self.header(headers::CONTENT_TYPE, "text/plain; charset=utf-8").body(content)
+    }
+
+    pub fn json<T: Serialize + ?Sized>(mut self, value: &T) -> ResponseBuilder {
+        match serde_json::to_string(value) {
+            Ok(content) => self.header(headers::CONTENT_TYPE, "application/json").body(content),
+            Err(e) => {
+                self.error.get_or_insert(e.into());
+                self
+            }
+        }
+    }
+
+    pub fn build(self) -> Result<Response, HttpError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.response),
+        }
+    }
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// Writes the head and, for an in-memory body, the body as text. File and
+/// stream bodies are left out.
 impl fmt::Display for Response {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}{}{}{}{}",
-            self.status_line, CRLF, self.headers, CRLF, self.body
-        )
+        write!(f, "{}", self.head())?;
+        if let ResponseBody::Full(content) = &self.body {
+            write!(f, "{}", String::from_utf8_lossy(content))?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,55 @@
+//! Sending file bodies straight from the page cache to a socket.
+
+use std::io;
+
+use tokio::{fs::File, net::TcpStream};
+
+/// Smaller files are sent through the buffered path, where they share a
+/// write with the head; the extra system calls would cost more than copying.
+pub const MIN_SIZE: u64 = 64 * 1024;
+
+/// Sends `length` bytes of `file` from `offset` to `socket` with
+/// `sendfile(2)`, so the data never passes through userspace.
+///
+/// Returns `Ok(false)` without sending anything if the file or socket don't
+/// support it, for the caller to fall back to copying.
+#[cfg(target_os = "linux")]
+pub async fn send_file(socket: &TcpStream, file: &File, offset: u64, length: u64) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    use tokio::io::Interest;
+
+    /// Linux sends at most this much per call.
+    const MAX_CHUNK: u64 = 0x7fff_f000;
+
+    let (socket_fd, file_fd) = (socket.as_raw_fd(), file.as_raw_fd());
+    let end = offset + length;
+    let mut position = offset as libc::off_t;
+    while (position as u64) < end {
+        let count = (end - position as u64).min(MAX_CHUNK) as usize;
+        let sent = socket
+            .async_io(Interest::WRITABLE, || {
+                // SAFETY: both descriptors stay open for the call, as `socket` and
+                // `file` are borrowed, and `position` is a valid `off_t`.
+                match unsafe { libc::sendfile(socket_fd, file_fd, &mut position, count) } {
+                    -1 => Err(io::Error::last_os_error()),
+                    sent => Ok(sent as u64),
+                }
+            })
+            .await;
+        match sent {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file shrank while being sent")),
+            Ok(_) => {}
+            Err(e) if position as u64 == offset && matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) => {
+                return Ok(false)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn send_file(_socket: &TcpStream, _file: &File, _offset: u64, _length: u64) -> io::Result<bool> {
+    Ok(false)
+}
@@ -0,0 +1,148 @@
+use std::{fmt::Write, io, time::Duration};
+
+use futures::{stream, Stream};
+use tokio::{
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use super::{headers, request::Request, response::Response};
+
+/// How many events may wait to be written before `EventSender::send` waits.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// A single Server-Sent Event.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    pub fn new(data: &str) -> Event {
+        Event {
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    /// Sets the event type, dispatched to `addEventListener(name, ...)` in browsers.
+    pub fn event(mut self, name: &str) -> Event {
+        self.event = Some(name.to_string());
+        self
+    }
+
+    /// Sets the id the client sends back as `Last-Event-ID` when it reconnects.
+    pub fn id(mut self, id: &str) -> Event {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Sets how long the client waits before reconnecting.
+    pub fn retry(mut self, retry: Duration) -> Event {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Formats the event for the `text/event-stream` body. Line breaks in
+    /// `data` become separate `data` lines; they are removed from the id and
+    /// event type, where they would end the field early.
+    pub fn format(&self) -> String {
+        let single_line = |value: &str| value.replace(['\r', '\n', '\0'], "");
+        let mut formatted = String::new();
+        if let Some(event) = &self.event {
+            let _ = writeln!(formatted, "event: {}", single_line(event));
+        }
+        if let Some(id) = &self.id {
+            let _ = writeln!(formatted, "id: {}", single_line(id));
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(formatted, "retry: {}", retry.as_millis());
+        }
+        for line in self.data.replace("\r\n", "\n").split(['\n', '\r']) {
+            let _ = writeln!(formatted, "data: {}", line);
+        }
+        formatted.push('\n');
+        formatted
+    }
+}
+
+/// The client has gone away; stop producing events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Disconnected;
+
+/// Sends events to one connected client.
+#[derive(Clone)]
+pub struct EventSender {
+    sender: mpsc::Sender<Event>,
+    last_event_id: Option<String>,
+}
+
+impl EventSender {
+    /// Queues an event, waiting while the client is behind. Fails once the
+    /// client has disconnected.
+    pub async fn send(&self, event: Event) -> Result<(), Disconnected> {
+        self.sender.send(event).await.map_err(|_| Disconnected)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Completes when the client disconnects.
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+
+    /// The `Last-Event-ID` the client sent when reconnecting, to resume from.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+}
+
+/// A `text/event-stream` response fed by an [`EventSender`].
+///
+/// Comment lines are sent whenever no event was written for `keep_alive`, so
+/// proxies keep the connection open and a disconnected client is noticed even
+/// when there is nothing to send.
+pub struct Sse {
+    receiver: mpsc::Receiver<Event>,
+    keep_alive: Duration,
+}
+
+impl Sse {
+    /// Creates the response side and the sender for `request`, taking
+    /// `Last-Event-ID` from its headers.
+    pub fn channel(request: &Request, keep_alive: Duration) -> (Sse, EventSender) {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let sender = EventSender {
+            sender,
+            last_event_id: request.get_header(headers::LAST_EVENT_ID).cloned(),
+        };
+        (Sse { receiver, keep_alive }, sender)
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = io::Result<Vec<u8>>> + Send {
+        let keep_alive = self.keep_alive;
+        stream::unfold(self.receiver, move |mut receiver| async move {
+            let deadline = Instant::now() + keep_alive;
+            tokio::select! {
+                event = receiver.recv() => {
+                    let event = event?;
+                    Some((Ok(event.format().into_bytes()), receiver))
+                }
+                _ = time::sleep_until(deadline) => Some((Ok(b": keep-alive\n\n".to_vec()), receiver)),
+            }
+        })
+    }
+
+    pub fn into_response(self) -> Response {
+        let mut response = Response::new();
+        response.add_header(headers::CONTENT_TYPE, "text/event-stream");
+        response.add_header(headers::CACHE_CONTROL, "no-cache");
+        response.set_body_stream(self.into_stream());
+        response
+    }
+}
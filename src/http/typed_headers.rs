@@ -0,0 +1,924 @@
+use std::{fmt::Write, time::SystemTime};
+
+use base64::{engine::general_purpose, Engine};
+
+use super::{
+    headers::{self, is_token},
+    ParseError,
+};
+
+/// A header with a structured value.
+///
+/// `parse` receives every field value of the header joined with `", "`, as a
+/// list-based header is equivalent to its values sent on separate lines.
+pub trait TypedHeader: Sized {
+    fn name() -> &'static str;
+    fn parse(value: &str) -> Result<Self, ParseError>;
+    fn format(&self) -> String;
+}
+
+/// Splits a comma-separated header list, ignoring commas inside quoted strings
+/// and dropping empty elements.
+pub fn split_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                items.push(value[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(value[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+fn unquote(value: &str) -> Result<String, ParseError> {
+    let value = value.trim();
+    match value.strip_prefix('"') {
+        Some(quoted) => {
+            let quoted = quoted.strip_suffix('"').ok_or(ParseError("invalid quoted string"))?;
+            let mut unquoted = String::new();
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unquoted.push(chars.next().ok_or(ParseError("invalid quoted string"))?),
+                    _ => unquoted.push(c),
+                }
+            }
+            Ok(unquoted)
+        }
+        None if is_token(value) => Ok(value.to_string()),
+        None => Err(ParseError("invalid quoted string")),
+    }
+}
+
+fn quote_if_needed(value: &str) -> String {
+    if is_token(value) {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Parses `;`-separated `name=value` parameters, lowercasing the names.
+fn parse_params(params: &str) -> Result<Vec<(String, String)>, ParseError> {
+    let mut parsed = Vec::new();
+    for param in params.split(';').map(str::trim).filter(|param| !param.is_empty()) {
+        let (name, value) = param.split_once('=').ok_or(ParseError("invalid parameter"))?;
+        let name = name.trim();
+        if !is_token(name) {
+            return Err(ParseError("invalid parameter"));
+        }
+        parsed.push((name.to_ascii_lowercase(), unquote(value)?));
+    }
+    Ok(parsed)
+}
+
+fn parse_media_type(value: &str) -> Result<(String, Vec<(String, String)>), ParseError> {
+    let (mime, params) = value.split_once(';').unwrap_or((value, ""));
+    let (type_, subtype) = mime.trim().split_once('/').ok_or(ParseError("invalid media type"))?;
+    if !is_token(type_) || !is_token(subtype) {
+        return Err(ParseError("invalid media type"));
+    }
+    Ok((format!("{}/{}", type_, subtype).to_ascii_lowercase(), parse_params(params)?))
+}
+
+fn format_params(value: &mut String, params: &[(String, String)]) {
+    for (name, param_value) in params {
+        let _ = write!(value, "; {}={}", name, quote_if_needed(param_value));
+    }
+}
+
+/// `Content-Type`, e.g. `text/html; charset=utf-8`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentType {
+    mime_type: String,
+    params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    pub fn new(mime_type: &str) -> ContentType {
+        ContentType {
+            mime_type: mime_type.to_ascii_lowercase(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn with_param(mut self, name: &str, value: &str) -> ContentType {
+        self.params.push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param_name, _)| param_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+}
+
+impl TypedHeader for ContentType {
+    fn name() -> &'static str {
+        headers::CONTENT_TYPE
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let (mime_type, params) = parse_media_type(value)?;
+        Ok(ContentType { mime_type, params })
+    }
+
+    fn format(&self) -> String {
+        let mut value = self.mime_type.clone();
+        format_params(&mut value, &self.params);
+        value
+    }
+}
+
+/// A single media range of an `Accept` header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    pub mime_type: String,
+    pub params: Vec<(String, String)>,
+    pub quality: f32,
+}
+
+impl MediaRange {
+    /// Returns how specifically this range matches `mime_type`, or `None` if
+    /// it does not match at all. `*/*` is 0, `type/*` is 1 and an exact match is 2.
+    fn specificity(&self, mime_type: &str) -> Option<u8> {
+        let (type_, _) = mime_type.split_once('/')?;
+        match self.mime_type.split_once('/')? {
+            ("*", "*") => Some(0),
+            (range_type, "*") if range_type.eq_ignore_ascii_case(type_) => Some(1),
+            _ if self.mime_type.eq_ignore_ascii_case(mime_type) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+/// `Accept`, with media ranges ordered by preference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accept {
+    media_ranges: Vec<MediaRange>,
+}
+
+impl Accept {
+    pub fn media_ranges(&self) -> &[MediaRange] {
+        &self.media_ranges
+    }
+
+    /// Returns the quality the client assigns to `mime_type`, using the most
+    /// specific matching range. An unlisted type has quality 0.
+    pub fn quality(&self, mime_type: &str) -> f32 {
+        self.media_ranges
+            .iter()
+            .filter_map(|range| range.specificity(mime_type).map(|specificity| (specificity, range.quality)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, quality)| quality)
+            .unwrap_or(0.0)
+    }
+
+    /// Picks the entry of `available` the client prefers most. Ties go to the
+    /// earlier entry; types with quality 0 are never picked.
+    pub fn preferred<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&str, f32)> = None;
+        for mime_type in available {
+            let quality = self.quality(mime_type);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((mime_type, quality));
+            }
+        }
+        best.map(|(mime_type, _)| mime_type)
+    }
+}
+
+pub(crate) fn parse_quality(value: &str) -> Result<f32, ParseError> {
+    let valid = match value.split_once('.') {
+        Some(("0", decimals)) => decimals.len() <= 3 && decimals.bytes().all(|b| b.is_ascii_digit()),
+        Some(("1", decimals)) => decimals.len() <= 3 && decimals.bytes().all(|b| b == b'0'),
+        None => value == "0" || value == "1",
+        _ => false,
+    };
+    if !valid {
+        return Err(ParseError("invalid quality value"));
+    }
+    value.parse().map_err(|_| ParseError("invalid quality value"))
+}
+
+impl TypedHeader for Accept {
+    fn name() -> &'static str {
+        headers::ACCEPT
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let mut media_ranges = Vec::new();
+        for item in split_list(value) {
+            let (mime_type, mut params) = parse_media_type(item)?;
+            let quality = match params.iter().position(|(name, _)| name == "q") {
+                Some(index) => parse_quality(&params.remove(index).1)?,
+                None => 1.0,
+            };
+            media_ranges.push(MediaRange {
+                mime_type,
+                params,
+                quality,
+            });
+        }
+        // A stable sort keeps the client's order between equally weighted ranges.
+        media_ranges.sort_by(|a, b| b.quality.total_cmp(&a.quality));
+        Ok(Accept { media_ranges })
+    }
+
+    fn format(&self) -> String {
+        let mut items = Vec::new();
+        for range in &self.media_ranges {
+            let mut item = range.mime_type.clone();
+            format_params(&mut item, &range.params);
+            if range.quality < 1.0 {
+                let _ = write!(item, "; q={}", range.quality);
+            }
+            items.push(item);
+        }
+        items.join(", ")
+    }
+}
+
+/// `Accept-Encoding`: content codings with their quality values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcceptEncoding {
+    codings: Vec<(String, f32)>,
+}
+
+impl AcceptEncoding {
+    pub fn codings(&self) -> &[(String, f32)] {
+        &self.codings
+    }
+
+    /// Returns the quality the client assigns to `coding`. Unlisted codings
+    /// take the quality of `*`; without one, only `identity` is acceptable.
+    pub fn quality(&self, coding: &str) -> f32 {
+        let listed = |name: &str| {
+            self.codings
+                .iter()
+                .find(|(listed, _)| listed.eq_ignore_ascii_case(name))
+                .map(|(_, quality)| *quality)
+        };
+        listed(coding)
+            .or_else(|| listed("*"))
+            .unwrap_or(if coding == "identity" { 1.0 } else { 0.0 })
+    }
+
+    /// Picks the coding in `available` the client prefers most. Ties go to the
+    /// earlier entry; codings with quality 0 are never picked.
+    pub fn preferred<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&str, f32)> = None;
+        for coding in available {
+            let quality = self.quality(coding);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((coding, quality));
+            }
+        }
+        best.map(|(coding, _)| coding)
+    }
+}
+
+impl TypedHeader for AcceptEncoding {
+    fn name() -> &'static str {
+        headers::ACCEPT_ENCODING
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let mut codings = Vec::new();
+        // An empty field is valid and means only `identity` is acceptable.
+        for item in split_list(value).into_iter().filter(|item| !item.is_empty()) {
+            let (coding, params) = item.split_once(';').unwrap_or((item, ""));
+            let coding = coding.trim();
+            if !is_token(coding) {
+                return Err(ParseError("invalid content coding"));
+            }
+            let quality = match parse_params(params)?.into_iter().find(|(name, _)| name == "q") {
+                Some((_, quality)) => parse_quality(&quality)?,
+                None => 1.0,
+            };
+            codings.push((coding.to_ascii_lowercase(), quality));
+        }
+        Ok(AcceptEncoding { codings })
+    }
+
+    fn format(&self) -> String {
+        let items: Vec<String> = self
+            .codings
+            .iter()
+            .map(|(coding, quality)| match quality {
+                quality if *quality < 1.0 => format!("{};q={}", coding, quality),
+                _ => coding.clone(),
+            })
+            .collect();
+        items.join(", ")
+    }
+}
+
+/// `Cache-Control`, as an ordered list of directives.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CacheControl {
+    directives: Vec<(String, Option<String>)>,
+}
+
+impl CacheControl {
+    pub fn new() -> CacheControl {
+        CacheControl::default()
+    }
+
+    /// Adds a directive, replacing an existing directive with the same name.
+    pub fn with(mut self, directive: &str, value: Option<&str>) -> CacheControl {
+        let directive = directive.to_ascii_lowercase();
+        self.directives.retain(|(name, _)| name != &directive);
+        self.directives.push((directive, value.map(str::to_string)));
+        self
+    }
+
+    pub fn has(&self, directive: &str) -> bool {
+        self.directives.iter().any(|(name, _)| name.eq_ignore_ascii_case(directive))
+    }
+
+    pub fn get(&self, directive: &str) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(directive))
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    pub fn max_age(&self) -> Option<u64> {
+        self.get("max-age")?.parse().ok()
+    }
+
+    pub fn no_cache(&self) -> bool {
+        self.has("no-cache")
+    }
+
+    pub fn no_store(&self) -> bool {
+        self.has("no-store")
+    }
+
+    pub fn no_transform(&self) -> bool {
+        self.has("no-transform")
+    }
+}
+
+impl TypedHeader for CacheControl {
+    fn name() -> &'static str {
+        headers::CACHE_CONTROL
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let mut directives = Vec::new();
+        for item in split_list(value) {
+            let (name, value) = match item.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(unquote(value)?)),
+                None => (item, None),
+            };
+            if !is_token(name) {
+                return Err(ParseError("invalid Cache-Control directive"));
+            }
+            directives.push((name.to_ascii_lowercase(), value));
+        }
+        Ok(CacheControl { directives })
+    }
+
+    fn format(&self) -> String {
+        self.directives
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{}={}", name, quote_if_needed(value)),
+                None => name.clone(),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// A single range of a `Range: bytes=...` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteRange {
+    /// `first-last`, both inclusive.
+    FromTo(u64, u64),
+    /// `first-`, up to the end of the representation.
+    From(u64),
+    /// `-length`, the final `length` bytes.
+    Last(u64),
+}
+
+impl ByteRange {
+    /// Resolves the range against a representation of `length` bytes,
+    /// returning the inclusive `(first, last)` offsets if it is satisfiable.
+    pub fn resolve(&self, length: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::FromTo(first, last) if first < length => Some((first, last.min(length - 1))),
+            ByteRange::From(first) if first < length => Some((first, length - 1)),
+            ByteRange::Last(suffix) if suffix > 0 && length > 0 => {
+                Some((length.saturating_sub(suffix), length - 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `Range`. Only the `bytes` unit is supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    ranges: Vec<ByteRange>,
+}
+
+impl Range {
+    pub fn bytes(ranges: Vec<ByteRange>) -> Range {
+        Range { ranges }
+    }
+
+    pub fn ranges(&self) -> &[ByteRange] {
+        &self.ranges
+    }
+}
+
+impl TypedHeader for Range {
+    fn name() -> &'static str {
+        headers::RANGE
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let (unit, ranges) = value.split_once('=').ok_or(ParseError("invalid Range header"))?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Err(ParseError("invalid Range header"));
+        }
+        let parse_position = |position: &str| -> Result<u64, ParseError> {
+            if position.is_empty() || !position.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseError("invalid Range header"));
+            }
+            position.parse().map_err(|_| ParseError("invalid Range header"))
+        };
+        let mut parsed = Vec::new();
+        for range in split_list(ranges) {
+            let (first, last) = range.split_once('-').ok_or(ParseError("invalid Range header"))?;
+            parsed.push(match (first.trim(), last.trim()) {
+                ("", suffix) => ByteRange::Last(parse_position(suffix)?),
+                (first, "") => ByteRange::From(parse_position(first)?),
+                (first, last) => {
+                    let (first, last) = (parse_position(first)?, parse_position(last)?);
+                    if last < first {
+                        return Err(ParseError("invalid Range header"));
+                    }
+                    ByteRange::FromTo(first, last)
+                }
+            });
+        }
+        if parsed.is_empty() {
+            return Err(ParseError("invalid Range header"));
+        }
+        Ok(Range { ranges: parsed })
+    }
+
+    fn format(&self) -> String {
+        let ranges: Vec<String> = self
+            .ranges
+            .iter()
+            .map(|range| match range {
+                ByteRange::FromTo(first, last) => format!("{}-{}", first, last),
+                ByteRange::From(first) => format!("{}-", first),
+                ByteRange::Last(suffix) => format!("-{}", suffix),
+            })
+            .collect();
+        format!("bytes={}", ranges.join(","))
+    }
+}
+
+/// `Content-Range` for the `bytes` unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentRange {
+    /// `bytes first-last/length`, with `*` for an unknown length.
+    Bytes { first: u64, last: u64, length: Option<u64> },
+    /// `bytes */length`, sent with 416.
+    Unsatisfied(u64),
+}
+
+impl TypedHeader for ContentRange {
+    fn name() -> &'static str {
+        headers::CONTENT_RANGE
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError("invalid Content-Range header");
+        let (unit, range) = value.trim().split_once(' ').ok_or_else(invalid)?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return Err(invalid());
+        }
+        let (range, length) = range.trim().split_once('/').ok_or_else(invalid)?;
+        let number = |value: &str| -> Result<u64, ParseError> {
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            value.parse().map_err(|_| invalid())
+        };
+        let length = match length {
+            "*" => None,
+            length => Some(number(length)?),
+        };
+        match (range, length) {
+            ("*", Some(length)) => Ok(ContentRange::Unsatisfied(length)),
+            ("*", None) => Err(invalid()),
+            (range, length) => {
+                let (first, last) = range.split_once('-').ok_or_else(invalid)?;
+                let (first, last) = (number(first)?, number(last)?);
+                if last < first || length.is_some_and(|length| last >= length) {
+                    return Err(invalid());
+                }
+                Ok(ContentRange::Bytes { first, last, length })
+            }
+        }
+    }
+
+    fn format(&self) -> String {
+        match self {
+            ContentRange::Bytes { first, last, length: Some(length) } => format!("bytes {}-{}/{}", first, last, length),
+            ContentRange::Bytes { first, last, length: None } => format!("bytes {}-{}/*", first, last),
+            ContentRange::Unsatisfied(length) => format!("bytes */{}", length),
+        }
+    }
+}
+
+/// `If-Range`: the validator a range request is conditional on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfRange {
+    ETag(EntityTag),
+    Date(SystemTime),
+}
+
+impl TypedHeader for IfRange {
+    fn name() -> &'static str {
+        headers::IF_RANGE
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let value = value.trim();
+        if value.starts_with('"') || value.starts_with("W/") {
+            EntityTag::parse(value).map(IfRange::ETag)
+        } else {
+            parse_http_date(value).map(IfRange::Date)
+        }
+    }
+
+    fn format(&self) -> String {
+        match self {
+            IfRange::ETag(etag) => etag.to_string(),
+            IfRange::Date(date) => format_http_date(*date),
+        }
+    }
+}
+
+/// An entity tag such as `"v1"` or `W/"v1"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub weak: bool,
+    pub tag: String,
+}
+
+impl EntityTag {
+    pub fn strong(tag: &str) -> EntityTag {
+        EntityTag {
+            weak: false,
+            tag: tag.to_string(),
+        }
+    }
+
+    pub fn weak(tag: &str) -> EntityTag {
+        EntityTag {
+            weak: true,
+            tag: tag.to_string(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<EntityTag, ParseError> {
+        let value = value.trim();
+        let (weak, opaque_tag) = match value.strip_prefix("W/") {
+            Some(opaque_tag) => (true, opaque_tag),
+            None => (false, value),
+        };
+        let tag = opaque_tag
+            .strip_prefix('"')
+            .and_then(|tag| tag.strip_suffix('"'))
+            .ok_or(ParseError("invalid entity tag"))?;
+        if tag.contains('"') {
+            return Err(ParseError("invalid entity tag"));
+        }
+        Ok(EntityTag {
+            weak,
+            tag: tag.to_string(),
+        })
+    }
+
+    /// Strong comparison: both tags are strong and identical.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison: the opaque tags are identical.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl std::fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+impl TypedHeader for EntityTag {
+    fn name() -> &'static str {
+        headers::ETAG
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        EntityTag::parse(value)
+    }
+
+    fn format(&self) -> String {
+        self.to_string()
+    }
+}
+
+fn parse_entity_tags(value: &str) -> Result<Vec<EntityTag>, ParseError> {
+    let tags = split_list(value)
+        .into_iter()
+        .map(EntityTag::parse)
+        .collect::<Result<Vec<EntityTag>, ParseError>>()?;
+    if tags.is_empty() {
+        return Err(ParseError("empty entity tag list"));
+    }
+    Ok(tags)
+}
+
+fn format_entity_tags(tags: &[EntityTag]) -> String {
+    tags.iter().map(EntityTag::to_string).collect::<Vec<String>>().join(", ")
+}
+
+/// `If-Match`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfMatch {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl IfMatch {
+    /// Whether `etag` matches, using the strong comparison RFC 9110 requires
+    /// for `If-Match`.
+    pub fn matches(&self, etag: &EntityTag) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Tags(tags) => tags.iter().any(|tag| tag.strong_eq(etag)),
+        }
+    }
+}
+
+impl TypedHeader for IfMatch {
+    fn name() -> &'static str {
+        headers::IF_MATCH
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        if value.trim() == "*" {
+            return Ok(IfMatch::Any);
+        }
+        parse_entity_tags(value).map(IfMatch::Tags)
+    }
+
+    fn format(&self) -> String {
+        match self {
+            IfMatch::Any => "*".to_string(),
+            IfMatch::Tags(tags) => format_entity_tags(tags),
+        }
+    }
+}
+
+/// `If-None-Match`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfNoneMatch {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl IfNoneMatch {
+    /// Whether `etag` matches, using the weak comparison RFC 9110 requires
+    /// for `If-None-Match`.
+    pub fn matches(&self, etag: &EntityTag) -> bool {
+        match self {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Tags(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        }
+    }
+}
+
+impl TypedHeader for IfNoneMatch {
+    fn name() -> &'static str {
+        headers::IF_NONE_MATCH
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        if value.trim() == "*" {
+            return Ok(IfNoneMatch::Any);
+        }
+        parse_entity_tags(value).map(IfNoneMatch::Tags)
+    }
+
+    fn format(&self) -> String {
+        match self {
+            IfNoneMatch::Any => "*".to_string(),
+            IfNoneMatch::Tags(tags) => format_entity_tags(tags),
+        }
+    }
+}
+
+/// `Authorization` credentials.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authorization {
+    Basic { username: String, password: String },
+    Bearer(String),
+    Other { scheme: String, credentials: String },
+}
+
+impl TypedHeader for Authorization {
+    fn name() -> &'static str {
+        headers::AUTHORIZATION
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let (scheme, credentials) = value.trim().split_once(' ').ok_or(ParseError("invalid Authorization header"))?;
+        let credentials = credentials.trim();
+        if !is_token(scheme) || credentials.is_empty() {
+            return Err(ParseError("invalid Authorization header"));
+        }
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = general_purpose::STANDARD
+                .decode(credentials)
+                .map_err(|_| ParseError("invalid Authorization header"))?;
+            let decoded = String::from_utf8(decoded).map_err(|_| ParseError("invalid Authorization header"))?;
+            let (username, password) = decoded.split_once(':').ok_or(ParseError("invalid Authorization header"))?;
+            Ok(Authorization::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            })
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            Ok(Authorization::Bearer(credentials.to_string()))
+        } else {
+            Ok(Authorization::Other {
+                scheme: scheme.to_string(),
+                credentials: credentials.to_string(),
+            })
+        }
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Authorization::Basic { username, password } => format!(
+                "Basic {}",
+                general_purpose::STANDARD.encode(format!("{}:{}", username, password))
+            ),
+            Authorization::Bearer(token) => format!("Bearer {}", token),
+            Authorization::Other { scheme, credentials } => format!("{} {}", scheme, credentials),
+        }
+    }
+}
+
+/// `Host`, e.g. `example.com:8080` or `[::1]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Host {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl TypedHeader for Host {
+    fn name() -> &'static str {
+        headers::HOST
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        let value = value.trim();
+        let (host, port) = if value.starts_with('[') {
+            let end = value.find(']').ok_or(ParseError("invalid Host header"))?;
+            let port = match &value[end + 1..] {
+                "" => None,
+                rest => Some(rest.strip_prefix(':').ok_or(ParseError("invalid Host header"))?),
+            };
+            (&value[..=end], port)
+        } else {
+            match value.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (value, None),
+            }
+        };
+        if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '/' || c == '@') {
+            return Err(ParseError("invalid Host header"));
+        }
+        let port = match port {
+            Some(port) => Some(port.parse().map_err(|_| ParseError("invalid Host header"))?),
+            None => None,
+        };
+        Ok(Host {
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    fn format(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}", self.host, port),
+            None => self.host.clone(),
+        }
+    }
+}
+
+/// `Date`, formatted as an IMF-fixdate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Date(pub SystemTime);
+
+impl Date {
+    pub fn now() -> Date {
+        Date(SystemTime::now())
+    }
+}
+
+pub(crate) fn parse_http_date(value: &str) -> Result<SystemTime, ParseError> {
+    httpdate::parse_http_date(value.trim()).map_err(|_| ParseError("invalid HTTP date"))
+}
+
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+impl TypedHeader for Date {
+    fn name() -> &'static str {
+        headers::DATE
+    }
+
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        parse_http_date(value).map(Date)
+    }
+
+    fn format(&self) -> String {
+        format_http_date(self.0)
+    }
+}
+
+/// Defines a header holding a single HTTP date, like `Date`.
+macro_rules! http_date_headers {
+    ($($(#[$doc:meta])* $header:ident = $name:expr;)*) => {
+        $(
+            $(#[$doc])*
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            pub struct $header(pub SystemTime);
+
+            impl TypedHeader for $header {
+                fn name() -> &'static str {
+                    $name
+                }
+
+                fn parse(value: &str) -> Result<Self, ParseError> {
+                    parse_http_date(value).map($header)
+                }
+
+                fn format(&self) -> String {
+                    format_http_date(self.0)
+                }
+            }
+        )*
+    };
+}
+
+http_date_headers! {
+    /// `Last-Modified`.
+    LastModified = headers::LAST_MODIFIED;
+    /// `If-Modified-Since`.
+    IfModifiedSince = headers::IF_MODIFIED_SINCE;
+    /// `If-Unmodified-Since`.
+    IfUnmodifiedSince = headers::IF_UNMODIFIED_SINCE;
+    /// `Expires`.
+    Expires = headers::EXPIRES;
+}
@@ -0,0 +1,526 @@
+use std::{error::Error, fmt, io};
+
+use base64::{engine::general_purpose, Engine};
+use sha1::{Digest, Sha1};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::{
+    errors::{ClientError, HttpError},
+    headers,
+    request::{Method, Request},
+    response::{Response, Status},
+    typed_headers::split_list,
+    ParseError,
+};
+
+/// Appended to `Sec-WebSocket-Key` before hashing it into `Sec-WebSocket-Accept`.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const VERSION: &str = "13";
+const MAX_CONTROL_PAYLOAD: usize = 125;
+const READ_SIZE: usize = 8 * 1024;
+
+/// Status codes for close frames, from the IANA WebSocket Close Code Number Registry.
+pub mod close_code {
+    pub const NORMAL: u16 = 1000;
+    pub const GOING_AWAY: u16 = 1001;
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    pub const POLICY_VIOLATION: u16 = 1008;
+    pub const MESSAGE_TOO_BIG: u16 = 1009;
+    pub const INTERNAL_ERROR: u16 = 1011;
+
+    /// Whether `code` may be sent in a close frame. 1005, 1006 and 1015 are
+    /// reserved for reporting and never appear on the wire.
+    pub fn is_valid(code: u16) -> bool {
+        matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
+
+/// Bounds on incoming frames and messages. Anything larger closes the
+/// connection with 1009.
+#[derive(Debug, Clone)]
+pub struct WebSocketLimits {
+    pub max_frame_size: usize,
+    /// The size of a whole message, after joining its fragments.
+    pub max_message_size: usize,
+}
+
+impl Default for WebSocketLimits {
+    fn default() -> Self {
+        WebSocketLimits {
+            max_frame_size: 16 * 1024 * 1024,
+            max_message_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Checks that `request` is a WebSocket opening handshake and builds the
+/// `101 Switching Protocols` response accepting it.
+///
+/// Requests that don't ask for an upgrade, or ask for a version other than
+/// 13, are answered with 426 and the headers the client needs to retry.
+pub fn accept(request: &Request) -> Result<Response, HttpError> {
+    if request.get_method() != &Method::GET || request.get_http_version() != "HTTP/1.1" {
+        return Err(ParseError("WebSocket handshakes must be HTTP/1.1 GET requests").into());
+    }
+    let request_headers = request.get_headers();
+    let has_token = |name: &str, token: &str| {
+        request_headers
+            .get_all(name)
+            .flat_map(|value| split_list(value))
+            .any(|item| item.eq_ignore_ascii_case(token))
+    };
+    if !has_token(headers::UPGRADE, "websocket") || !has_token(headers::CONNECTION, "upgrade") {
+        return Err(HttpError::from(ClientError::UpgradeRequired)
+            .with_message("expected a WebSocket upgrade")
+            .with_detail("expected a WebSocket upgrade")
+            .with_header(headers::UPGRADE, "websocket")
+            .with_header(headers::CONNECTION, "Upgrade"));
+    }
+    if request_headers.get(headers::SEC_WEBSOCKET_VERSION).map(|version| version.trim()) != Some(VERSION) {
+        return Err(HttpError::from(ClientError::UpgradeRequired)
+            .with_message("unsupported WebSocket version")
+            .with_detail("unsupported WebSocket version")
+            .with_header(headers::SEC_WEBSOCKET_VERSION, VERSION));
+    }
+    let keys: Vec<&String> = request_headers.get_all(headers::SEC_WEBSOCKET_KEY).collect();
+    let key = match keys.as_slice() {
+        [key] => key.trim(),
+        _ => return Err(ParseError("expected exactly one Sec-WebSocket-Key").into()),
+    };
+    match general_purpose::STANDARD.decode(key) {
+        Ok(nonce) if nonce.len() == 16 => {}
+        _ => return Err(ParseError("invalid Sec-WebSocket-Key").into()),
+    }
+
+    let mut response = Response::new();
+    response.set_status_code(Status::SwitchingProtocols);
+    response.remove_header(headers::CONTENT_LENGTH);
+    response.add_header(headers::UPGRADE, "websocket");
+    response.add_header(headers::CONNECTION, "Upgrade");
+    response.add_header(headers::SEC_WEBSOCKET_ACCEPT, &accept_key(key));
+    Ok(response)
+}
+
+/// The `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(ACCEPT_GUID.as_bytes());
+    general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    pub fn new(code: u16, reason: &str) -> CloseFrame {
+        CloseFrame {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// A close frame, with its status code and reason if it had one.
+    Close(Option<CloseFrame>),
+}
+
+#[derive(Debug)]
+pub enum WebSocketError {
+    /// A frame broke RFC 6455. Received frames close the connection with 1002.
+    Protocol(&'static str),
+    /// A text message or close reason was not UTF-8; closed with 1007.
+    InvalidUtf8,
+    /// A frame or message was over the [`WebSocketLimits`]; closed with 1009.
+    MessageTooBig,
+    /// The connection has been closed, or we have sent a close frame.
+    Closed,
+    Io(io::Error),
+}
+
+impl WebSocketError {
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            WebSocketError::Protocol(_) => Some(close_code::PROTOCOL_ERROR),
+            WebSocketError::InvalidUtf8 => Some(close_code::INVALID_PAYLOAD),
+            WebSocketError::MessageTooBig => Some(close_code::MESSAGE_TOO_BIG),
+            WebSocketError::Closed | WebSocketError::Io(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebSocketError::Protocol(message) => write!(f, "WebSocket protocol error: {}", message),
+            WebSocketError::InvalidUtf8 => write!(f, "WebSocket text is not valid UTF-8"),
+            WebSocketError::MessageTooBig => write!(f, "WebSocket message too big"),
+            WebSocketError::Closed => write!(f, "WebSocket closed"),
+            WebSocketError::Io(e) => write!(f, "WebSocket I/O error: {}", e),
+        }
+    }
+}
+
+impl Error for WebSocketError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WebSocketError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WebSocketError {
+    fn from(e: io::Error) -> Self {
+        WebSocketError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OpCode {
+    Continuation = 0x0,
+    Text = 0x1,
+    Binary = 0x2,
+    Close = 0x8,
+    Ping = 0x9,
+    Pong = 0xA,
+}
+
+impl OpCode {
+    fn from_u8(opcode: u8) -> Option<OpCode> {
+        match opcode {
+            0x0 => Some(OpCode::Continuation),
+            0x1 => Some(OpCode::Text),
+            0x2 => Some(OpCode::Binary),
+            0x8 => Some(OpCode::Close),
+            0x9 => Some(OpCode::Ping),
+            0xA => Some(OpCode::Pong),
+            _ => None,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        self as u8 & 0x8 != 0
+    }
+}
+
+struct Frame {
+    fin: bool,
+    opcode: OpCode,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Open,
+    CloseSent,
+    Closed,
+}
+
+fn encode_frame(buffer: &mut Vec<u8>, opcode: OpCode, payload: &[u8]) {
+    buffer.push(0x80 | opcode as u8);
+    match payload.len() {
+        length if length < 126 => buffer.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            buffer.push(126);
+            buffer.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            buffer.push(127);
+            buffer.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    buffer.extend_from_slice(payload);
+}
+
+fn close_payload(frame: &Option<CloseFrame>) -> Vec<u8> {
+    match frame {
+        Some(frame) => {
+            let mut payload = frame.code.to_be_bytes().to_vec();
+            payload.extend_from_slice(frame.reason.as_bytes());
+            payload
+        }
+        None => Vec::new(),
+    }
+}
+
+fn parse_close(payload: &[u8]) -> Result<Option<CloseFrame>, WebSocketError> {
+    match payload {
+        [] => Ok(None),
+        [_] => Err(WebSocketError::Protocol("close frame with a one-byte payload")),
+        [high, low, reason @ ..] => {
+            let code = u16::from_be_bytes([*high, *low]);
+            if !close_code::is_valid(code) {
+                return Err(WebSocketError::Protocol("invalid close code"));
+            }
+            let reason = std::str::from_utf8(reason).map_err(|_| WebSocketError::InvalidUtf8)?;
+            Ok(Some(CloseFrame::new(code, reason)))
+        }
+    }
+}
+
+fn data_message(opcode: OpCode, payload: Vec<u8>) -> Result<Message, WebSocketError> {
+    match opcode {
+        OpCode::Text => String::from_utf8(payload)
+            .map(Message::Text)
+            .map_err(|_| WebSocketError::InvalidUtf8),
+        _ => Ok(Message::Binary(payload)),
+    }
+}
+
+/// One side of a WebSocket connection, after the opening handshake.
+///
+/// Pings are answered automatically and a close frame from the client is
+/// echoed back before [`WebSocket::recv`] returns it. Outgoing messages are
+/// sent as single frames; incoming fragments are joined into one message.
+///
+/// `recv` and `send` are cancellation safe, so they can be used in
+/// `tokio::select!` to wait for the client and another source at once.
+pub struct WebSocket<S = TcpStream> {
+    stream: S,
+    limits: WebSocketLimits,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+    /// The opcode and payload so far of a fragmented message.
+    fragments: Option<(OpCode, Vec<u8>)>,
+    state: State,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocket<S> {
+    /// Wraps a connection whose opening handshake has completed.
+    pub fn from_stream(stream: S, limits: WebSocketLimits) -> WebSocket<S> {
+        WebSocket::from_buffered_stream(stream, Vec::new(), limits)
+    }
+
+    /// Like [`WebSocket::from_stream`], for when `buffered` bytes were read
+    /// from the connection after the handshake, as the start of its frames.
+    pub fn from_buffered_stream(stream: S, buffered: Vec<u8>, limits: WebSocketLimits) -> WebSocket<S> {
+        WebSocket {
+            stream,
+            limits,
+            read_buffer: buffered,
+            write_buffer: Vec::new(),
+            fragments: None,
+            state: State::Open,
+        }
+    }
+
+    /// Waits for the next message. Returns `None` once the connection is
+    /// closed. An error closes the connection, with the matching close code
+    /// if the client broke the protocol.
+    pub async fn recv(&mut self) -> Option<Result<Message, WebSocketError>> {
+        loop {
+            if self.state == State::Closed {
+                return None;
+            }
+            if let Err(e) = self.flush_pending().await {
+                self.state = State::Closed;
+                return Some(Err(e.into()));
+            }
+            let frame = match self.next_frame() {
+                Ok(frame) => frame,
+                Err(e) => return Some(Err(self.fail(e).await)),
+            };
+            if let Some(frame) = frame {
+                match self.handle_frame(frame) {
+                    Ok(Some(message)) => {
+                        if self.state == State::Closed {
+                            let _ = self.flush_pending().await;
+                            let _ = self.stream.shutdown().await;
+                        }
+                        return Some(Ok(message));
+                    }
+                    // A fragment; the rest of the message may already be buffered.
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(self.fail(e).await)),
+                }
+            }
+            if self.read_buffer.len() == self.read_buffer.capacity() {
+                self.read_buffer.reserve(READ_SIZE);
+            }
+            match self.stream.read_buf(&mut self.read_buffer).await {
+                Ok(0) => {
+                    self.state = State::Closed;
+                    return None;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.state = State::Closed;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+
+    /// Sends `message` as a single frame. Sending a close frame starts the
+    /// close handshake; keep calling `recv` until it returns the client's
+    /// close frame.
+    pub async fn send(&mut self, message: Message) -> Result<(), WebSocketError> {
+        if self.state != State::Open {
+            return Err(WebSocketError::Closed);
+        }
+        match &message {
+            Message::Text(text) => encode_frame(&mut self.write_buffer, OpCode::Text, text.as_bytes()),
+            Message::Binary(data) => encode_frame(&mut self.write_buffer, OpCode::Binary, data),
+            Message::Ping(data) | Message::Pong(data) if data.len() > MAX_CONTROL_PAYLOAD => {
+                return Err(WebSocketError::Protocol("control frame payload over 125 bytes"));
+            }
+            Message::Ping(data) => encode_frame(&mut self.write_buffer, OpCode::Ping, data),
+            Message::Pong(data) => encode_frame(&mut self.write_buffer, OpCode::Pong, data),
+            Message::Close(frame) => {
+                let payload = close_payload(frame);
+                if payload.len() > MAX_CONTROL_PAYLOAD {
+                    return Err(WebSocketError::Protocol("close reason over 123 bytes"));
+                }
+                encode_frame(&mut self.write_buffer, OpCode::Close, &payload);
+                self.state = State::CloseSent;
+            }
+        }
+        self.flush_pending().await?;
+        Ok(())
+    }
+
+    /// Starts the close handshake with `code` and `reason`.
+    pub async fn close(&mut self, code: u16, reason: &str) -> Result<(), WebSocketError> {
+        self.send(Message::Close(Some(CloseFrame::new(code, reason)))).await
+    }
+
+    /// Writes queued frames, keeping whatever is left if cancelled part way.
+    async fn flush_pending(&mut self) -> io::Result<()> {
+        while !self.write_buffer.is_empty() {
+            let written = self.stream.write(&self.write_buffer).await?;
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.write_buffer.drain(..written);
+        }
+        self.stream.flush().await
+    }
+
+    /// Closes the connection after a bad frame, telling the client why.
+    async fn fail(&mut self, e: WebSocketError) -> WebSocketError {
+        if let (State::Open, Some(code)) = (self.state, e.close_code()) {
+            encode_frame(&mut self.write_buffer, OpCode::Close, &code.to_be_bytes());
+        }
+        self.state = State::Closed;
+        let _ = self.flush_pending().await;
+        let _ = self.stream.shutdown().await;
+        e
+    }
+
+    /// Takes the next complete frame off the read buffer, checking its header
+    /// as soon as it has arrived so oversized frames aren't buffered.
+    fn next_frame(&mut self) -> Result<Option<Frame>, WebSocketError> {
+        let buffer = &self.read_buffer;
+        if buffer.len() < 2 {
+            return Ok(None);
+        }
+        let fin = buffer[0] & 0x80 != 0;
+        if buffer[0] & 0x70 != 0 {
+            return Err(WebSocketError::Protocol("reserved bits set without an extension"));
+        }
+        let opcode = OpCode::from_u8(buffer[0] & 0x0F).ok_or(WebSocketError::Protocol("reserved opcode"))?;
+        if buffer[1] & 0x80 == 0 {
+            return Err(WebSocketError::Protocol("client frames must be masked"));
+        }
+        let (length, mut header_length) = match buffer[1] & 0x7F {
+            126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as u64, 4),
+            127 if buffer.len() >= 10 => {
+                let length = u64::from_be_bytes(buffer[2..10].try_into().unwrap());
+                if length >> 63 != 0 {
+                    return Err(WebSocketError::Protocol("payload length has its high bit set"));
+                }
+                (length, 10)
+            }
+            126 | 127 => return Ok(None),
+            length => (length as u64, 2),
+        };
+        if opcode.is_control() {
+            if !fin {
+                return Err(WebSocketError::Protocol("fragmented control frame"));
+            }
+            if length > MAX_CONTROL_PAYLOAD as u64 {
+                return Err(WebSocketError::Protocol("control frame payload over 125 bytes"));
+            }
+        } else {
+            let buffered = self.fragments.as_ref().map_or(0, |(_, payload)| payload.len() as u64);
+            if length > self.limits.max_frame_size as u64
+                || buffered + length > self.limits.max_message_size as u64
+            {
+                return Err(WebSocketError::MessageTooBig);
+            }
+        }
+        let mask = match buffer.get(header_length..header_length + 4) {
+            Some(mask) => [mask[0], mask[1], mask[2], mask[3]],
+            None => return Ok(None),
+        };
+        header_length += 4;
+        let frame_length = header_length + length as usize;
+        if buffer.len() < frame_length {
+            self.read_buffer.reserve(frame_length - buffer.len());
+            return Ok(None);
+        }
+        let mut payload: Vec<u8> = self.read_buffer.drain(..frame_length).skip(header_length).collect();
+        for (index, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[index % 4];
+        }
+        Ok(Some(Frame { fin, opcode, payload }))
+    }
+
+    fn handle_frame(&mut self, frame: Frame) -> Result<Option<Message>, WebSocketError> {
+        match frame.opcode {
+            OpCode::Text | OpCode::Binary => {
+                if self.fragments.is_some() {
+                    return Err(WebSocketError::Protocol("expected a continuation frame"));
+                }
+                if frame.fin {
+                    return data_message(frame.opcode, frame.payload).map(Some);
+                }
+                self.fragments = Some((frame.opcode, frame.payload));
+                Ok(None)
+            }
+            OpCode::Continuation => {
+                let (opcode, mut payload) = self
+                    .fragments
+                    .take()
+                    .ok_or(WebSocketError::Protocol("continuation frame without a message"))?;
+                payload.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    return data_message(opcode, payload).map(Some);
+                }
+                self.fragments = Some((opcode, payload));
+                Ok(None)
+            }
+            OpCode::Ping => {
+                if self.state == State::Open {
+                    encode_frame(&mut self.write_buffer, OpCode::Pong, &frame.payload);
+                }
+                Ok(Some(Message::Ping(frame.payload)))
+            }
+            OpCode::Pong => Ok(Some(Message::Pong(frame.payload))),
+            OpCode::Close => {
+                let close = parse_close(&frame.payload)?;
+                if self.state == State::Open {
+                    let code = close.as_ref().map_or(Vec::new(), |close| close.code.to_be_bytes().to_vec());
+                    encode_frame(&mut self.write_buffer, OpCode::Close, &code);
+                }
+                self.state = State::Closed;
+                Ok(Some(Message::Close(close)))
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::http::websocket::Message;
+
+/// Named channels that broadcast WebSocket messages to every subscriber.
+///
+/// Each subscriber has its own queue of `capacity` messages. A subscriber that
+/// falls further behind misses the oldest messages and is told how many by
+/// [`Subscription::recv`], so one slow client never holds up a channel or
+/// grows memory without bound. Channels are created by the first subscriber
+/// and dropped with the last.
+pub struct Hub {
+    channels: Mutex<HashMap<String, broadcast::Sender<Message>>>,
+    capacity: usize,
+}
+
+impl Hub {
+    pub fn new(capacity: usize) -> Arc<Hub> {
+        Arc::new(Hub {
+            channels: Mutex::new(HashMap::new()),
+            capacity,
+        })
+    }
+
+    /// Subscribes to `channel`; dropping the subscription unsubscribes.
+    pub fn subscribe(self: &Arc<Self>, channel: &str) -> Subscription {
+        let receiver = self
+            .channels()
+            .entry(channel.to_string())
+            .or_insert_with(|| broadcast::channel(self.capacity).0)
+            .subscribe();
+        Subscription {
+            hub: self.clone(),
+            channel: channel.to_string(),
+            receiver,
+        }
+    }
+
+    /// Queues `message` for every subscriber of `channel`, without waiting for
+    /// any of them. Returns how many subscribers it was queued for.
+    pub fn broadcast(&self, channel: &str, message: Message) -> usize {
+        match self.channels().get(channel) {
+            Some(sender) => sender.send(message).unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    pub fn subscriber_count(&self, channel: &str) -> usize {
+        self.channels().get(channel).map_or(0, |sender| sender.receiver_count())
+    }
+
+    /// The number of subscribers of every channel that has any.
+    pub fn subscriber_counts(&self) -> HashMap<String, usize> {
+        self.channels()
+            .iter()
+            .map(|(channel, sender)| (channel.clone(), sender.receiver_count()))
+            .collect()
+    }
+
+    fn channels(&self) -> MutexGuard<'_, HashMap<String, broadcast::Sender<Message>>> {
+        // The map is consistent after every operation, so a panic elsewhere can't leave it broken.
+        self.channels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A subscriber that fell behind and missed this many messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lagged(pub u64);
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "subscriber missed {} messages", self.0)
+    }
+}
+
+impl Error for Lagged {}
+
+/// Messages broadcast to one channel of a [`Hub`].
+pub struct Subscription {
+    hub: Arc<Hub>,
+    channel: String,
+    receiver: broadcast::Receiver<Message>,
+}
+
+impl Subscription {
+    pub fn channel(&self) -> &str {
+        &self.channel
+    }
+
+    /// Waits for the next message. If this subscriber fell behind, returns
+    /// [`Lagged`] once and then continues with the oldest message still
+    /// queued; handlers that can't tolerate gaps can close the connection
+    /// instead. Cancellation safe.
+    pub async fn recv(&mut self) -> Result<Message, Lagged> {
+        match self.receiver.recv().await {
+            Ok(message) => Ok(message),
+            Err(RecvError::Lagged(missed)) => Err(Lagged(missed)),
+            Err(RecvError::Closed) => unreachable!("the hub keeps channels with subscribers open"),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut channels = self.hub.channels();
+        if channels
+            .get(&self.channel)
+            .is_some_and(|sender| sender.receiver_count() <= 1)
+        {
+            channels.remove(&self.channel);
+        }
+    }
+}
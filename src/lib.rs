@@ -1,5 +1,13 @@
+// Lets source generated by `embed::Embed` name the crate from inside it too.
+extern crate self as yaw;
+
 pub mod server;
 pub mod config;
+pub mod embed;
+pub mod file_cache;
+pub mod file_source;
+pub mod hub;
+pub mod mount;
 
 mod request_processor;
 pub mod http;
@@ -1,23 +1,35 @@
-use std::{thread, time::Duration};
+use std::{fs, thread, time::Duration};
 
-use tokio;
 use yaw::{
     config::Config,
-    http::{request::Method, response::Status},
+    http::{
+        errors::ErrorTemplates,
+        response::{Redirect, Response, Status},
+        sse::Event,
+        websocket::{close_code, Message},
+    },
     server::Server,
 };
 
-use hyper::Client;
 use url_builder::URLBuilder;
 
 #[tokio::main]
 async fn main() {
     let mut server = match Server::new() {
         Ok(server) => server,
-        Err(e) => panic!("error while creating the server"),
+        Err(e) => panic!("error while creating the server: {}", e),
     };
     let mut config = Config::default();
     config.set_index("hello.html");
+    for (status, page) in [
+        (Status::Unauthorized, "401.html"),
+        (Status::NotFound, "404.html"),
+        (Status::NotImplemented, "501.html"),
+    ] {
+        if let Ok(template) = fs::read_to_string(config.root_path.clone() + page) {
+            config.set_error_page(status, &template);
+        }
+    }
     config.add_request_handler("/sleep", |request, response| {
         thread::sleep(Duration::from_secs(
             request
@@ -31,8 +43,7 @@ async fn main() {
     });
 
     config.add_request_handler("/hello", |request, response| {
-        response.set_status_code(Status::Ok);
-        response.set_body(format!("Hello {} \r\n",request.get_param("name").unwrap_or(&"None".to_string())));
+        *response = Response::text(format!("Hello {} \r\n",request.get_param("name").unwrap_or(&"None".to_string())));
     });
 
     config.add_request_handler("/login", |request, response| {
@@ -46,11 +57,73 @@ async fn main() {
         .add_param("redirect_uri", "http://lvh.me:8080/callback")
         .add_param("state", "some token")
         .add_param("nonce", "0394852-3190485-2490358");
-        response.set_status_code(Status::Found);
-        response.add_header("Location",&url.build())
+        *response = match Response::redirect(&url.build(), Redirect::Found) {
+            Ok(redirect) => redirect,
+            Err(http_error) => http_error.to_response_for(request, &ErrorTemplates::default()),
+        };
+    });
+
+    config.add_sse_handler("/ticks", |_request, events| {
+        tokio::spawn(async move {
+            let mut tick: u64 = events
+                .last_event_id()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(0);
+            loop {
+                tick += 1;
+                let event = Event::new(&format!("tick {}", tick)).id(&tick.to_string());
+                if events.send(event).await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        });
+    });
+
+    config.add_websocket_handler("/echo", |_request, mut socket, _hub| {
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = socket.recv().await {
+                if let Message::Text(_) | Message::Binary(_) = message {
+                    if socket.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+    });
+
+    config.add_websocket_handler("/chat", |request, mut socket, hub| {
+        let room = request.get_param("room").cloned().unwrap_or_else(|| "lobby".to_string());
+        let mut subscription = hub.subscribe(&room);
+        let hub = hub.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = socket.recv() => match message {
+                        Some(Ok(message @ Message::Text(_))) => {
+                            hub.broadcast(&room, message);
+                        }
+                        Some(Ok(_)) => {}
+                        _ => break,
+                    },
+                    message = subscription.recv() => match message {
+                        Ok(message) => {
+                            if socket.send(message).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(_lagged) => {
+                            let _ = socket.close(close_code::POLICY_VIOLATION, "too slow").await;
+                            break;
+                        }
+                    },
+                }
+            }
+        });
     });
 
     server.set_config(config);
-    server.run().await;
-    print!("server is running")
+    if let Err(e) = server.run().await {
+        panic!("error while running the server: {}", e)
+    }
 }
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use crate::{
+    file_source::{FileSource, FsSource},
+    http::caching::CacheRule,
+};
+
+/// Whether files and directories whose names start with a dot are served.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HiddenFiles {
+    /// Served when asked for by name, but left out of directory listings.
+    Serve,
+    /// Served and listed like any other file.
+    List,
+    /// Answered with 404 and left out of directory listings.
+    Deny,
+}
+
+/// How a [`Mount`] serves its directory.
+#[derive(Debug, Clone)]
+pub struct MountOptions {
+    /// Files served for a directory, tried in order.
+    pub index_files: Vec<String>,
+    /// Lists directories without an index file instead of answering 404.
+    pub autoindex: bool,
+    /// Tried before the rules in `Config::cache_rules`.
+    pub cache_rules: Vec<CacheRule>,
+    pub hidden_files: HiddenFiles,
+}
+
+impl Default for MountOptions {
+    fn default() -> Self {
+        MountOptions {
+            index_files: vec!["index.html".to_string()],
+            autoindex: false,
+            cache_rules: Vec::new(),
+            hidden_files: HiddenFiles::Serve,
+        }
+    }
+}
+
+/// Serves the files of a [`FileSource`] under the URL prefix `prefix`, so
+/// with the prefix `/assets` and the directory `build/assets`,
+/// `/assets/app.js` is `build/assets/app.js`.
+#[derive(Clone)]
+pub struct Mount {
+    prefix: String,
+    source: Arc<dyn FileSource>,
+    pub options: MountOptions,
+}
+
+impl Mount {
+    /// Serves `directory` of the file system.
+    pub fn new(prefix: &str, directory: &str, options: MountOptions) -> Mount {
+        Mount::with_source(prefix, Arc::new(FsSource::new(directory)), options)
+    }
+
+    pub fn with_source(prefix: &str, source: Arc<dyn FileSource>, options: MountOptions) -> Mount {
+        Mount {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            source,
+            options,
+        }
+    }
+
+    /// The prefix without a trailing slash, empty for `/`.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn source(&self) -> &dyn FileSource {
+        self.source.as_ref()
+    }
+
+    /// The part of `path` below the prefix, starting with `/` unless it is
+    /// the prefix itself, or `None` if `path` isn't under the prefix.
+    pub fn strip_prefix<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let rest = path.strip_prefix(self.prefix.as_str())?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    }
+}
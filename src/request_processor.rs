@@ -1,47 +1,78 @@
-use std::{fs, sync::Arc};
+use std::{collections::HashMap, io, path::Path, sync::Arc};
 
 use crate::{
-    config::Config,
+    config::{Config, TrailingSlash},
+    file_cache::CachedSource,
+    file_source::{FileSource, SourceFile, SourceMetadata},
+    hub::Hub,
+    mount::{HiddenFiles, Mount, MountOptions},
     http::{
         auth::basic_auth_validate,
+        autoindex,
+        caching,
+        compression::{self, ContentCoding},
+        conditional::{self, Precondition},
         errors::{ClientError, HttpError, ServerError},
-        request::{Method, Request},
-        response::{self, Response},
+        headers, ranges,
+        request::{self, Method, Request},
+        response,
+        sse::Sse,
+        typed_headers::{Accept, AcceptEncoding, ContentRange, LastModified, Range, TypedHeader},
+        websocket::{self, WebSocket},
     },
 };
 
+/// What a request path resolved to among handler routes.
+enum Route<'a, T> {
+    Handler(&'a T),
+    /// The path differs from a route by a trailing slash.
+    Redirect(String),
+}
+
 pub struct RequestProcessor {
     config: Arc<Config>,
+    hub: Arc<Hub>,
+    /// `root_path`, serving everything outside `config.mounts`.
+    root: Mount,
 }
 
 impl RequestProcessor {
-    pub fn new(config: Arc<Config>) -> Self {
-        Self {
-            config: config,
-        }
+    pub fn new(config: Arc<Config>, hub: Arc<Hub>) -> Self {
+        let hidden_files = if config.autoindex.show_hidden { HiddenFiles::List } else { HiddenFiles::Serve };
+        let root = Mount::new(
+            "/",
+            &config.root_path,
+            MountOptions {
+                index_files: config.index_files.clone(),
+                autoindex: false,
+                cache_rules: Vec::new(),
+                hidden_files,
+            },
+        );
+        Self { config, hub, root }
+    }
+
+    pub fn get_config(&self) -> &Config {
+        &self.config
     }
 
     pub fn process(&self, request: Request) -> Result<response::Response, HttpError> {
         let mut response = response::Response::new();
 
-        self.handle_authentication(&request, &mut response)?;
+        self.handle_authentication(&request)?;
         self.handle_routes(&request, &mut response)?;
+        caching::apply(&self.config.cache_rules, request.get_path(), &request, &mut response);
+        let accept_encoding = request.typed_header::<AcceptEncoding>();
+        compression::compress(&mut response, accept_encoding.as_ref(), &self.config.compression);
 
         Ok(response)
     }
 
-    fn handle_authentication(
-        &self,
-        request: &Request,
-        response: &mut response::Response,
-    ) -> Result<(), HttpError> {
-        match (request.get_method(), request.get_path()) {
-            (Method::GET, "/admin") => {
-                if !basic_auth_validate(request, &self.config.username, &self.config.password) {
-                    return Err(HttpError::ClientError(ClientError::Unauthorized))
-                }
+    fn handle_authentication(&self, request: &Request) -> Result<(), HttpError> {
+        if let (Method::GET, "/admin") = (request.get_method(), request.get_path()) {
+            if !basic_auth_validate(request, &self.config.username, &self.config.password) {
+                return Err(HttpError::from(ClientError::Unauthorized))
             }
-            (_, _) => {}
         }
         Ok(())
     }
@@ -54,24 +85,60 @@ impl RequestProcessor {
         if response.get_status_code() == &response::Status::Ok {
             match (request.get_method(), request.get_path()) {
                 (self::Method::GET, "/admin") => {
-                    let file_name = "admin.html";
-                    self.load_content_from_file(response, file_name)?
+                    let file_name = "/admin.html";
+                    self.load_content_from_file(request, response, &self.root, file_name)?
                 }
-                (Method::GET, "/") => {
-                    let file_name = self.config.index.as_str();
-                    self.load_content_from_file(response, file_name)?
-                },
                 (self::Method::GET, _) => {
-                    let file_name = request.get_path();
-                    for (path,handler) in &self.config.request_handlers {
-                        if path == &request.get_path() {
-                            handler(request,response);
+                    let mount = self.find_mount(request.get_path());
+                    if let Some((mount, file_name)) = mount {
+                        if self.source(mount).metadata(file_name).is_ok() {
+                            return self.load_content_from_file(request, response, mount, file_name);
+                        }
+                    }
+                    match self.find_route(&self.config.request_handlers, request) {
+                        Some(Route::Handler(handler)) => {
+                            handler(request, response);
+                            return Ok(())
+                        }
+                        Some(Route::Redirect(path)) => return Self::redirect(request, response, &path),
+                        None => {}
+                    }
+                    match self.find_route(&self.config.websocket_handlers, request) {
+                        Some(Route::Handler(&handler)) => {
+                            *response = websocket::accept(request)?;
+                            let request = request.clone();
+                            let limits = self.config.websocket_limits.clone();
+                            let hub = self.hub.clone();
+                            response.on_upgrade(move |stream, buffered| {
+                                handler(&request, WebSocket::from_buffered_stream(stream, buffered, limits), &hub)
+                            });
+                            return Ok(())
+                        }
+                        Some(Route::Redirect(path)) => return Self::redirect(request, response, &path),
+                        None => {}
+                    }
+                    match self.find_route(&self.config.sse_handlers, request) {
+                        Some(Route::Handler(handler)) => {
+                            let (sse, events) = Sse::channel(request, self.config.sse_keep_alive);
+                            handler(request, events);
+                            *response = sse.into_response();
                             return Ok(())
                         }
+                        Some(Route::Redirect(path)) => return Self::redirect(request, response, &path),
+                        None => {}
+                    }
+                    let (mount, file_name) = mount.unwrap_or((&self.root, request.get_path()));
+                    match self.load_content_from_file(request, response, mount, file_name) {
+                        Err(e) if e.status() == response::Status::NotFound && self.accepts_spa_fallback(request) => {
+                            let fallback = self.config.spa_fallback.as_deref().unwrap_or_default();
+                            // Headers the failed attempt added, such as its cache rules, are not the fallback's.
+                            *response = response::Response::new();
+                            self.load_content_from_file(request, response, &self.root, &format!("/{}", fallback))?
+                        }
+                        result => result?,
                     }
-                    self.load_content_from_file(response, file_name)?;
                 },
-                (_, _) => return Err(HttpError::ServerError(ServerError::MethodNotImplemented)),
+                (_, _) => return Err(HttpError::from(ServerError::MethodNotImplemented)),
             };
         }
         Ok(())
@@ -79,26 +146,231 @@ impl RequestProcessor {
 
     fn load_content_from_file(
         &self,
+        request: &Request,
         response: &mut response::Response,
+        mount: &Mount,
         file_name: &str,
     ) -> Result<(), HttpError> {
-        let root_path = &self.config.root_path;
-
-        match fs::metadata(root_path.to_string() + file_name) {
-            Ok(_) => match fs::read_to_string(root_path.to_string() + file_name) {
-                Ok(contents) => {
-                    response.set_body(contents);
+        let segments = || file_name.split('/');
+        if segments().any(|segment| segment == "..")
+            || (mount.options.hidden_files == HiddenFiles::Deny && segments().any(|segment| segment.starts_with('.')))
+        {
+            return Err(HttpError::from(ClientError::NotFound).with_message(format!("{} is not served", file_name)));
+        }
+        let source = &self.source(mount);
+        let mut file_name = file_name.to_string();
+        if source.metadata(&file_name).is_ok_and(|metadata| metadata.is_dir) {
+            // Relative links in an index file only resolve against the directory with the slash.
+            if !request.get_path().ends_with('/') {
+                return Self::redirect(request, response, &format!("{}/", request.get_path()));
+            }
+            if !file_name.ends_with('/') {
+                file_name.push('/');
+            }
+            let index = mount.options.index_files.iter().find(|index| {
+                source.metadata(&format!("{}{}", file_name, index)).is_ok_and(|metadata| !metadata.is_dir)
+            });
+            match index {
+                Some(index) => file_name.push_str(index),
+                None if mount.options.autoindex || self.config.autoindex.is_enabled(request.get_path()) => {
+                    return self.list_directory(request, response, mount, &file_name)
                 }
-                Err(e) => {
-                    print!("{}", e);
-                    return Err(HttpError::ServerError(ServerError::InternalServerError));
+                None => {
+                    return Err(HttpError::from(ClientError::NotFound)
+                        .with_message(format!("{}{} has no index file", mount.prefix(), file_name)))
                 }
-            },
-            Err(file_not_found_err) => {
-                print!("{}", file_not_found_err);
-                return Err(HttpError::ClientError(ClientError::NotFound));
             }
+        }
+        let url_path = format!("{}{}", mount.prefix(), file_name);
+        caching::apply(&mount.options.cache_rules, &url_path, request, response);
+        caching::apply(&self.config.cache_rules, &url_path, request, response);
+
+        let (file, metadata) = match self.open_precompressed(request, response, source, &file_name) {
+            Some((file, metadata, coding)) => {
+                response.add_header(headers::CONTENT_ENCODING, coding.as_str());
+                (file, metadata)
+            }
+            None => source.open(&file_name).map_err(|e| Self::file_error(&url_path, e))?,
+        };
+        if metadata.is_dir {
+            return Err(HttpError::from(ClientError::NotFound).with_message(format!("{} is not a file", url_path)));
+        }
+        let etag = self.config.file_etags.for_version(&metadata.version);
+        let last_modified = metadata.modified;
+        if let Some(etag) = &etag {
+            response.set_typed_header(etag);
+        }
+        if let Some(last_modified) = last_modified {
+            response.set_typed_header(&LastModified(last_modified));
+        }
+        match conditional::evaluate(request, etag.as_ref(), last_modified) {
+            Precondition::Proceed => {}
+            Precondition::NotModified => {
+                response.set_status_code(response::Status::NotModified);
+                response.remove_header(headers::CONTENT_LENGTH);
+                let accept_encoding = request.typed_header::<AcceptEncoding>();
+                let content_type = source.content_type(&file_name);
+                let options = &self.config.compression;
+                compression::match_not_modified(response, content_type, metadata.len, accept_encoding.as_ref(), options);
+                return Ok(());
+            }
+            Precondition::Failed => return Err(HttpError::from(ClientError::PreconditionFailed)),
+        }
+        let content_type = source.content_type(&file_name);
+        response.add_header(headers::CONTENT_TYPE, content_type);
+        response.add_header(headers::ACCEPT_RANGES, "bytes");
+
+        let length = metadata.len;
+        let ranges = match request.typed_header::<Range>() {
+            Some(range) if conditional::if_range_matches(request, etag.as_ref(), last_modified) => {
+                ranges::resolve(&range, length)
+            }
+            _ => None,
         };
+        match ranges.as_deref() {
+            None => response.set_response_body(file.into_body(0, length)),
+            Some([]) => {
+                return Err(HttpError::from(ClientError::RangeNotSatisfiable)
+                    .with_header(headers::CONTENT_RANGE, &ContentRange::Unsatisfied(length).format()))
+            }
+            Some(&[(first, last)]) => {
+                response.set_status_code(response::Status::PartialContent);
+                response.set_typed_header(&ContentRange::Bytes {
+                    first,
+                    last,
+                    length: Some(length),
+                });
+                response.set_response_body(file.into_body(first, last - first + 1));
+            }
+            Some(ranges) => {
+                let (body, multipart_type) = ranges::multipart_body(&file, ranges, length, content_type)
+                    .map_err(|e| Self::file_error(&url_path, e))?;
+                response.set_status_code(response::Status::PartialContent);
+                response.add_header(headers::CONTENT_TYPE, &multipart_type);
+                response.set_response_body(body);
+            }
+        }
+        Ok(())
+    }
+
+    /// The files of `mount`, through the file cache if there is one.
+    fn source<'a>(&'a self, mount: &'a Mount) -> CachedSource<'a> {
+        CachedSource::new(mount.source(), self.config.file_cache.as_deref())
+    }
+
+    /// The mount with the longest prefix matching `path`, with the part of
+    /// `path` below the prefix.
+    fn find_mount<'a, 'p>(&'a self, path: &'p str) -> Option<(&'a Mount, &'p str)> {
+        self.config
+            .mounts
+            .iter()
+            .filter_map(|mount| mount.strip_prefix(path).map(|file_name| (mount, file_name)))
+            .max_by_key(|(mount, _)| mount.prefix().len())
+    }
+
+    /// Whether a missing path should get the single-page application document:
+    /// a fallback is configured, the path has no extension and the client
+    /// accepts HTML, as browsers navigating do.
+    fn accepts_spa_fallback(&self, request: &Request) -> bool {
+        let file_name = request.get_path().rsplit('/').next().unwrap_or_default();
+        self.config.spa_fallback.is_some()
+            && Path::new(file_name).extension().is_none()
+            && request.typed_header::<Accept>().is_some_and(|accept| {
+                accept
+                    .media_ranges()
+                    .iter()
+                    .any(|range| range.mime_type.eq_ignore_ascii_case("text/html") && range.quality > 0.0)
+            })
+    }
+
+    /// Finds the handler for the request path in `routes`, taking
+    /// [`TrailingSlash`] into account when the path isn't registered as is.
+    fn find_route<'a, T>(&self, routes: &'a HashMap<String, T>, request: &Request) -> Option<Route<'a, T>> {
+        let path = request.get_path();
+        if let Some(handler) = routes.get(path) {
+            return Some(Route::Handler(handler));
+        }
+        let alternative = match path.strip_suffix('/') {
+            Some(stripped) if !stripped.is_empty() => stripped.to_string(),
+            Some(_) => return None,
+            None => format!("{}/", path),
+        };
+        let handler = routes.get(&alternative)?;
+        match self.config.trailing_slash {
+            TrailingSlash::Exact => None,
+            TrailingSlash::Redirect => Some(Route::Redirect(alternative)),
+            TrailingSlash::Ignore => Some(Route::Handler(handler)),
+        }
+    }
+
+    /// Answers with a 301 to `path`, keeping the query string.
+    fn redirect(request: &Request, response: &mut response::Response, path: &str) -> Result<(), HttpError> {
+        let location = match request.get_query() {
+            Some(query) => format!("{}?{}", request::encode_path(path), query),
+            None => request::encode_path(path),
+        };
+        *response = response::Response::redirect(&location, response::Redirect::MovedPermanently)?;
         Ok(())
     }
+
+    fn list_directory(
+        &self,
+        request: &Request,
+        response: &mut response::Response,
+        mount: &Mount,
+        path: &str,
+    ) -> Result<(), HttpError> {
+        let show_hidden = mount.options.hidden_files == HiddenFiles::List;
+        let mut entries = mount
+            .source()
+            .read_dir(path)
+            .map_err(|e| Self::file_error(&format!("{}{}", mount.prefix(), path), e))?;
+        entries.retain(|entry| show_hidden || !entry.name.starts_with('.'));
+        *response = autoindex::listing(request, entries)?;
+        Ok(())
+    }
+
+    /// Opens the precompressed sibling of `path` the client prefers, such as
+    /// `app.js.br`. If any sibling exists, the response varies by
+    /// `Accept-Encoding` even when the plain file is sent.
+    fn open_precompressed(
+        &self,
+        request: &Request,
+        response: &mut response::Response,
+        source: &dyn FileSource,
+        path: &str,
+    ) -> Option<(SourceFile, SourceMetadata, ContentCoding)> {
+        let accept_encoding = request.typed_header::<AcceptEncoding>();
+        let mut has_siblings = false;
+        let mut best: Option<(String, ContentCoding, f32)> = None;
+        for &coding in &self.config.compression.precompressed {
+            let sibling = match coding.extension() {
+                Some(extension) => format!("{}.{}", path, extension),
+                None => continue,
+            };
+            if !source.metadata(&sibling).is_ok_and(|metadata| !metadata.is_dir) {
+                continue;
+            }
+            has_siblings = true;
+            let quality = accept_encoding.as_ref().map_or(0.0, |accept_encoding| accept_encoding.quality(coding.as_str()));
+            if quality > 0.0 && best.as_ref().is_none_or(|(_, _, best_quality)| quality > *best_quality) {
+                best = Some((sibling, coding, quality));
+            }
+        }
+        if has_siblings {
+            compression::add_vary(response);
+        }
+        let (sibling, coding, _) = best?;
+        let (file, metadata) = source.open(&sibling).ok()?;
+        Some((file, metadata, coding))
+    }
+
+    fn file_error(path: &str, e: io::Error) -> HttpError {
+        let error = match e.kind() {
+            io::ErrorKind::NotFound => ClientError::NotFound.into(),
+            io::ErrorKind::PermissionDenied => ClientError::Forbidden.into(),
+            _ => HttpError::from(ServerError::InternalServerError),
+        };
+        error.with_message(format!("unable to read {}", path)).with_source(e)
+    }
 }
@@ -1,27 +1,38 @@
-use std::{sync::Arc, thread, time::Duration};
+use std::sync::Arc;
 
-use tokio::net::{TcpListener, TcpStream};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
 
 use crate::{
     config::Config,
+    file_cache::CacheStats,
+    hub::Hub,
     http::{
-        request::{self, Request, Method},
-        response::{Response, Status},
+        errors::HttpError,
+        headers,
+        request::Request,
+        response::Response,
+        typed_headers::Accept,
     },
-    request_processor::{RequestProcessor},
+    request_processor::RequestProcessor,
 };
 
 pub struct Server {
     config: Arc<Config>,
+    hub: Arc<Hub>,
     request_processor: Arc<RequestProcessor>,
 }
 
 impl Server {
     pub fn new() -> Result<Server, Box<dyn std::error::Error>> {
         let config = Arc::new(Config::default());
+        let hub = Hub::new(config.hub_capacity);
         Ok(Server {
             config: config.clone(),
-            request_processor: Arc::new(RequestProcessor::new(config.clone())),
+            hub: hub.clone(),
+            request_processor: Arc::new(RequestProcessor::new(config.clone(), hub)),
         })
     }
 
@@ -39,31 +50,66 @@ impl Server {
 
     pub fn set_config(&mut self, config: Config) {
         self.config = Arc::new(config);
-        self.request_processor = Arc::new(RequestProcessor::new(self.config.clone()))
+        self.hub = Hub::new(self.config.hub_capacity);
+        self.request_processor = Arc::new(RequestProcessor::new(self.config.clone(), self.hub.clone()))
+    }
+
+    /// The hub WebSocket handlers subscribe to, for broadcasting from outside
+    /// a connection or reading subscriber counts. Replaced by `set_config`.
+    pub fn hub(&self) -> Arc<Hub> {
+        self.hub.clone()
     }
 
-    pub async fn handle_connection(mut stream: TcpStream, request_processor: Arc<RequestProcessor>) {
-        let mut response = Response::new();
-        let request: Request;
+    /// Hit and miss counts of the file cache, if `Config::enable_file_cache` was used.
+    pub fn file_cache_stats(&self) -> Option<CacheStats> {
+        self.config.file_cache.as_ref().map(|cache| cache.stats())
+    }
 
-        match request::Request::load(&mut stream).await {
-            Ok(request_from_stream) => request = request_from_stream,
+    pub async fn handle_connection(stream: TcpStream, request_processor: Arc<RequestProcessor>) {
+        let limits = &request_processor.get_config().request_limits;
+        let mut reader = BufReader::new(stream);
+        let request = match Request::load_buffered(&mut reader, limits).await {
+            Ok(request_from_stream) => request_from_stream,
             Err(http_error) => {
-                response = http_error.to_response();
-                response.send_to(stream).await;
+                Self::log_error(&http_error);
+                // The framing of whatever follows is unknown, so never reuse the connection.
+                let mut response = http_error.to_response();
+                response.add_header(headers::CONNECTION, "close");
+                Self::send(response, reader.into_inner(), "HTTP/1.1").await;
                 return;
             }
-        }
-        match request_processor.process(request) {
-            Ok(response_from_processor) => response = response_from_processor,
+        };
+        let accept = request.typed_header::<Accept>();
+        let http_version = request.get_http_version().to_string();
+        let mut response = match request_processor.process(request) {
+            Ok(response_from_processor) => response_from_processor,
             Err(http_error) => {
-                response = http_error.to_response();
-                response.send_to(stream).await;
-                return;
+                Self::log_error(&http_error);
+                let templates = &request_processor.get_config().error_templates;
+                http_error.to_negotiated_response(accept.as_ref(), templates)
             }
+        };
+        if let Some(upgrade) = response.take_upgrade() {
+            // The client may not wait for the 101 before sending its first frames.
+            let buffered = reader.buffer().to_vec();
+            let mut stream = reader.into_inner();
+            match response.send_to(&mut stream, &http_version).await {
+                Ok(()) => upgrade(stream, buffered),
+                Err(e) => eprintln!("error while sending the response: {}", e),
+            }
+            return;
         }
-        response.send_to(stream).await;
+        Self::send(response, reader.into_inner(), &http_version).await;
+    }
+
+    fn log_error(http_error: &HttpError) {
+        eprintln!("{}", http_error);
     }
 
-    
+    async fn send(response: Response, mut stream: TcpStream, client_version: &str) {
+        if let Err(e) = response.send_to_tcp(&mut stream, client_version).await {
+            eprintln!("error while sending the response: {}", e);
+        }
+        let _ = stream.shutdown().await;
+    }
 }
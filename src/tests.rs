@@ -1,7 +1,122 @@
+use std::{
+    net::TcpStream,
+    sync::{Arc, Once, OnceLock},
+    thread,
+    time::Duration,
+};
+
 use curl::easy::Easy;
 
+use crate::{
+    config::Config,
+    http::{
+        sse::Event,
+        websocket::{close_code, Message},
+    },
+    hub::Hub,
+    server::Server,
+};
+
+mod common;
+mod compression;
+mod embed;
+mod file_cache;
+mod errors;
+mod hub;
+mod headers;
+mod request_parsing;
+mod request_smuggling;
+mod response_builder;
+mod response_body;
+mod sse;
+mod static_files;
+mod status;
+mod typed_headers;
+mod websocket;
+
+static SERVER: Once = Once::new();
+/// The test server's hub, set once it has started.
+static HUB: OnceLock<Arc<Hub>> = OnceLock::new();
+
+/// Starts a server on port 8080 for the tests in this module, once per test run.
+fn start_server() {
+    SERVER.call_once(|| {
+        thread::spawn(|| {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let mut server = Server::new().unwrap();
+                let mut config = Config::default();
+                config.set_index("hello.html");
+                config.add_sse_handler("/events", |_request, events| {
+                    tokio::spawn(async move {
+                        let first: u32 = events
+                            .last_event_id()
+                            .and_then(|id| id.parse().ok())
+                            .map_or(1, |id: u32| id + 1);
+                        for id in first..first + 3 {
+                            let event = Event::new(&format!("build {}", id)).id(&id.to_string());
+                            if events.send(event).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                });
+                config.add_websocket_handler("/echo", |_request, mut socket, _hub| {
+                    tokio::spawn(async move {
+                        while let Some(Ok(message)) = socket.recv().await {
+                            if let Message::Text(_) | Message::Binary(_) = message {
+                                let _ = socket.send(message).await;
+                            }
+                        }
+                    });
+                });
+                config.add_websocket_handler("/chat", |request, mut socket, hub| {
+                    let room = request.get_param("room").cloned().unwrap_or_else(|| "lobby".to_string());
+                    let mut subscription = hub.subscribe(&room);
+                    let hub = hub.clone();
+                    tokio::spawn(async move {
+                        loop {
+                            tokio::select! {
+                                message = socket.recv() => match message {
+                                    Some(Ok(message @ Message::Text(_))) => {
+                                        hub.broadcast(&room, message);
+                                    }
+                                    Some(Ok(_)) => {}
+                                    _ => break,
+                                },
+                                message = subscription.recv() => match message {
+                                    Ok(message) => {
+                                        if socket.send(message).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                    Err(_lagged) => {
+                                        let _ = socket.close(close_code::POLICY_VIOLATION, "too slow").await;
+                                        break;
+                                    }
+                                },
+                            }
+                        }
+                    });
+                });
+                server.set_config(config);
+                let _ = HUB.set(server.hub());
+                server.run().await.unwrap();
+            });
+        });
+    });
+    for _ in 0..50 {
+        if TcpStream::connect("127.0.0.1:8080").is_ok() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("test server did not start");
+}
+
 #[test]
 fn ok() {
+    start_server();
     let mut curl = Easy::new();
     curl.url("http://localhost:8080/").unwrap();
     curl.perform().unwrap();
@@ -10,6 +125,7 @@ fn ok() {
 
 #[test]
 fn unauthorized() {
+    start_server();
     let mut curl = Easy::new();
     curl.url("http://localhost:8080/admin").unwrap();
     curl.perform().unwrap();
@@ -18,6 +134,7 @@ fn unauthorized() {
 
 #[test]
 fn authorized() {
+    start_server();
     let mut curl = Easy::new();
     curl.url("http://localhost:8080/admin").unwrap();
     curl.username("user").unwrap();
@@ -28,6 +145,7 @@ fn authorized() {
 
 #[test]
 fn method_not_implemented() {
+    start_server();
     let mut curl = Easy::new();
     curl.url("http://localhost:8080/hello.html").unwrap();
     curl.post(true).unwrap();
@@ -37,6 +155,7 @@ fn method_not_implemented() {
 
 #[test]
 fn not_found() {
+    start_server();
     let mut curl = Easy::new();
     curl.url("http://localhost:8080/non-existing-file.html").unwrap();
     curl.perform().unwrap();
@@ -0,0 +1,91 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use futures::TryStreamExt;
+
+use crate::{
+    config::Config,
+    http::{errors::HttpError, request::Request, response::Response},
+    hub::Hub,
+    request_processor::RequestProcessor,
+};
+
+/// A document root in a temporary directory, removed when dropped.
+pub(super) struct Site {
+    pub root: PathBuf,
+    pub config: Arc<Config>,
+    processor: RequestProcessor,
+}
+
+impl Site {
+    pub fn new(name: &str, files: &[(&str, &[u8])]) -> Site {
+        Site::with_config(name, files, Config::default())
+    }
+
+    /// The temporary directory of the site called `name`, for configuring mounts.
+    pub fn root(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("yaw-{}-{}", name, std::process::id()))
+    }
+
+    pub fn with_config(name: &str, files: &[(&str, &[u8])], mut config: Config) -> Site {
+        let root = Site::root(name);
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        config.root_path = format!("{}/", root.display());
+        let config = Arc::new(config);
+        Site {
+            root,
+            processor: RequestProcessor::new(config.clone(), Hub::new(1)),
+            config,
+        }
+    }
+
+    /// GETs `path` with extra header lines, each ending in CRLF.
+    pub async fn get(&self, path: &str, extra_headers: &str) -> Result<Response, HttpError> {
+        get(&self.processor, path, extra_headers).await
+    }
+
+    pub async fn get_with_version(
+        &self,
+        version: &str,
+        path: &str,
+        extra_headers: &str,
+    ) -> Result<Response, HttpError> {
+        get_with_version(&self.processor, version, path, extra_headers).await
+    }
+
+    /// The body of a successful GET of `path`.
+    pub async fn content(&self, path: &str) -> Vec<u8> {
+        body(&mut self.get(path, "").await.unwrap()).await
+    }
+}
+
+impl Drop for Site {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// GETs `path` from `processor` over HTTP/1.1, with extra header lines.
+pub(super) async fn get(processor: &RequestProcessor, path: &str, extra_headers: &str) -> Result<Response, HttpError> {
+    get_with_version(processor, "HTTP/1.1", path, extra_headers).await
+}
+
+pub(super) async fn get_with_version(
+    processor: &RequestProcessor,
+    version: &str,
+    path: &str,
+    extra_headers: &str,
+) -> Result<Response, HttpError> {
+    let raw = format!("GET {} {}\r\nHost: localhost\r\n{}\r\n", path, version, extra_headers);
+    let request = Request::load(&mut raw.as_bytes()).await.unwrap();
+    processor.process(request)
+}
+
+/// Collects the whole body of `response`, whatever kind it is.
+pub(super) async fn body(response: &mut Response) -> Vec<u8> {
+    let chunks: Vec<Vec<u8>> = response.take_body().into_stream().try_collect().await.unwrap();
+    chunks.concat()
+}
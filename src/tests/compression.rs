@@ -0,0 +1,165 @@
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use futures::stream;
+
+use crate::{
+    http::{
+        body::ResponseBody,
+        compression::{self, CompressionOptions},
+        headers, mime,
+        response::{Response, Status},
+        typed_headers::{AcceptEncoding, TypedHeader},
+    },
+    tests::common::{body, Site},
+};
+
+fn page() -> String {
+    "<p>compress me</p>\n".repeat(200)
+}
+
+fn compress(response: &mut Response, accept_encoding: &str) {
+    let accept_encoding = AcceptEncoding::parse(accept_encoding).ok().unwrap();
+    compression::compress(response, Some(&accept_encoding), &CompressionOptions::default());
+}
+
+fn decode(coding: &str, compressed: &[u8]) -> String {
+    let mut decoded = String::new();
+    match coding {
+        "gzip" => GzDecoder::new(compressed).read_to_string(&mut decoded),
+        "deflate" => ZlibDecoder::new(compressed).read_to_string(&mut decoded),
+        "br" => brotli::Decompressor::new(compressed, 4096).read_to_string(&mut decoded),
+        _ => panic!("unknown coding {}", coding),
+    }
+    .unwrap();
+    decoded
+}
+
+#[tokio::test]
+async fn negotiates_the_coding() {
+    for (accept_encoding, coding) in [
+        ("gzip, deflate, br", "br"),
+        ("gzip;q=1, br;q=0.5", "gzip"),
+        ("deflate", "deflate"),
+        ("*", "br"),
+    ] {
+        let mut response = Response::html(page());
+        compress(&mut response, accept_encoding);
+        assert_eq!(response.get_header(headers::CONTENT_ENCODING).unwrap(), coding);
+        assert_eq!(response.get_header(headers::VARY).unwrap(), "Accept-Encoding");
+        let compressed = body(&mut response).await;
+        assert_eq!(
+            response.get_header(headers::CONTENT_LENGTH).unwrap(),
+            &compressed.len().to_string()
+        );
+        assert!(compressed.len() < page().len());
+        assert_eq!(decode(coding, &compressed), page());
+    }
+}
+
+#[test]
+fn identity_only_still_varies() {
+    let mut response = Response::html(page());
+    compress(&mut response, "identity, br;q=0");
+    assert!(response.get_header(headers::CONTENT_ENCODING).is_none());
+    assert_eq!(response.get_header(headers::VARY).unwrap(), "Accept-Encoding");
+    assert_eq!(response.get_header(headers::CONTENT_LENGTH).unwrap(), &page().len().to_string());
+
+    let mut response = Response::html(page());
+    response.add_header(headers::VARY, "Accept-Language");
+    compression::compress(&mut response, None, &CompressionOptions::default());
+    assert_eq!(response.get_header(headers::VARY).unwrap(), "Accept-Language, Accept-Encoding");
+}
+
+#[test]
+fn skips_ineligible_responses() {
+    let small = Response::html("<p>tiny</p>");
+    let mut image = Response::new();
+    image.add_header(headers::CONTENT_TYPE, "image/png");
+    image.set_body_bytes(vec![0; 4096]);
+    let mut no_transform = Response::html(page());
+    no_transform.add_header(headers::CACHE_CONTROL, "public, no-transform");
+    let mut encoded = Response::html(page());
+    encoded.add_header(headers::CONTENT_ENCODING, "gzip");
+    let mut not_modified = Response::html(page());
+    not_modified.set_status_code(Status::NotModified);
+    for mut response in [small, image, no_transform, not_modified] {
+        compress(&mut response, "gzip");
+        assert!(response.get_header(headers::CONTENT_ENCODING).is_none());
+        assert!(response.get_header(headers::VARY).is_none());
+    }
+    compress(&mut encoded, "br");
+    assert_eq!(encoded.get_header(headers::CONTENT_ENCODING).unwrap(), "gzip");
+    let mut json = Response::json(&page()).unwrap();
+    compress(&mut json, "gzip");
+    assert_eq!(json.get_header(headers::CONTENT_ENCODING).unwrap(), "gzip");
+}
+
+#[test]
+fn weakens_strong_etags() {
+    let mut response = Response::html(page());
+    response.add_header(headers::ETAG, "\"v1\"");
+    compress(&mut response, "gzip");
+    assert_eq!(response.get_header(headers::ETAG).unwrap(), "W/\"v1\"");
+}
+
+#[test]
+fn event_streams_and_streams_are_not_compressed() {
+    let mut response = Response::new();
+    response.add_header(headers::CONTENT_TYPE, "text/event-stream");
+    response.set_body(page());
+    compress(&mut response, "gzip");
+    assert!(response.get_header(headers::CONTENT_ENCODING).is_none());
+
+    // An encoder would hold back what a live stream sends until it has enough to compress.
+    let mut response = Response::new();
+    response.add_header(headers::CONTENT_TYPE, "text/html");
+    response.set_body_stream(stream::iter([Ok(page().into_bytes())]));
+    compress(&mut response, "gzip");
+    assert!(response.get_header(headers::CONTENT_ENCODING).is_none());
+    assert!(matches!(response.get_body(), ResponseBody::Stream { .. }));
+}
+
+#[tokio::test]
+async fn not_modified_has_the_etag_of_the_compressed_file() {
+    let site = Site::new("compression-etag", &[("page.html", page().as_bytes())]);
+    let response = site.get("/page.html", "Accept-Encoding: gzip\r\n").await.unwrap();
+    let etag = response.get_header(headers::ETAG).unwrap().clone();
+    assert!(etag.starts_with("W/"));
+    let response = site
+        .get("/page.html", &format!("Accept-Encoding: gzip\r\nIf-None-Match: {}\r\n", etag))
+        .await
+        .unwrap();
+    assert_eq!(response.get_status_code(), &Status::NotModified);
+    assert_eq!(response.get_header(headers::ETAG), Some(&etag));
+    assert_eq!(response.get_header(headers::VARY).unwrap(), "Accept-Encoding");
+
+    // Sent as it is, the file keeps its strong tag.
+    let response = site.get("/page.html", &format!("If-None-Match: {}\r\n", etag)).await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::NotModified);
+    assert_eq!(response.get_header(headers::ETAG).unwrap(), &etag[2..]);
+}
+
+#[tokio::test]
+async fn files_are_compressed_while_sent() {
+    let path = std::env::temp_dir().join(format!("yaw-compression-{}.html", std::process::id()));
+    std::fs::write(&path, page()).unwrap();
+    let mut response = Response::new();
+    response.add_header(headers::CONTENT_TYPE, mime::from_path(path.to_str().unwrap()));
+    let file = tokio::fs::File::open(&path).await.unwrap();
+    response.set_body_file(file, page().len() as u64);
+    compress(&mut response, "br");
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(response.get_body(), ResponseBody::Stream { length: None, .. }));
+    assert!(response.get_header(headers::CONTENT_LENGTH).is_none());
+    assert_eq!(decode("br", &body(&mut response).await), page());
+}
+
+#[test]
+fn mime_types() {
+    assert_eq!(mime::from_path("root/index.html"), "text/html; charset=utf-8");
+    assert_eq!(mime::from_path("app.min.JS"), "text/javascript; charset=utf-8");
+    assert_eq!(mime::from_path("logo.svg"), "image/svg+xml");
+    assert_eq!(mime::from_path(".hidden"), mime::DEFAULT);
+    assert_eq!(mime::from_path("dir.d/README"), mime::DEFAULT);
+}
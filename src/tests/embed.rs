@@ -0,0 +1,141 @@
+use std::{fs, sync::Arc};
+
+use crate::{
+    config::Config,
+    embed::{Embed, EmbeddedFile},
+    http::{
+        compression::ContentCoding,
+        headers,
+        response::Status,
+        typed_headers::{EntityTag, LastModified},
+    },
+    hub::Hub,
+    mount::MountOptions,
+    request_processor::RequestProcessor,
+    tests::common::{body, get},
+};
+
+const APP: &[u8] = b"console.log('embedded');\n";
+
+static FILES: &[EmbeddedFile] = &[
+    EmbeddedFile {
+        path: "/index.html",
+        content: b"<h1>embedded</h1>",
+        content_type: "text/html; charset=utf-8",
+        version: "0a1b2c",
+        modified: Some(1_700_000_000),
+    },
+    EmbeddedFile {
+        path: "/js/app.js",
+        content: APP,
+        content_type: "text/javascript; charset=utf-8",
+        version: "3d4e5f",
+        modified: Some(1_700_000_000),
+    },
+    EmbeddedFile {
+        path: "/js/app.js.br",
+        content: b"brotli bytes",
+        content_type: "text/javascript; charset=utf-8",
+        version: "6a7b8c",
+        modified: Some(1_700_000_000),
+    },
+    EmbeddedFile {
+        path: "/js/vendor/lib.js",
+        content: b"lib",
+        content_type: "text/javascript; charset=utf-8",
+        version: "9d0e1f",
+        modified: None,
+    },
+];
+
+fn processor() -> RequestProcessor {
+    let mut config = Config::default();
+    config.add_embedded_mount(
+        "/static",
+        FILES,
+        MountOptions {
+            autoindex: true,
+            ..MountOptions::default()
+        },
+    );
+    RequestProcessor::new(Arc::new(config), Hub::new(1))
+}
+
+#[tokio::test]
+async fn serves_embedded_files() {
+    let processor = processor();
+    let mut response = get(&processor, "/static/js/app.js", "").await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::Ok);
+    assert_eq!(response.get_header(headers::CONTENT_TYPE).unwrap(), "text/javascript; charset=utf-8");
+    assert_eq!(response.get_header(headers::CONTENT_LENGTH).unwrap(), &APP.len().to_string());
+    assert_eq!(response.typed_header::<EntityTag>(), Some(EntityTag::strong("3d4e5f")));
+    assert_eq!(response.get_header(headers::LAST_MODIFIED).unwrap(), "Tue, 14 Nov 2023 22:13:20 GMT");
+    assert_eq!(response.get_header(headers::VARY).unwrap(), "Accept-Encoding");
+    assert_eq!(body(&mut response).await, APP);
+
+    let mut response = get(&processor, "/static/", "").await.unwrap();
+    assert_eq!(body(&mut response).await, b"<h1>embedded</h1>");
+    let response = get(&processor, "/static", "").await.unwrap();
+    assert_eq!(response.get_header(headers::LOCATION).unwrap(), "/static/");
+    assert_eq!(get(&processor, "/static/missing.js", "").await.err().unwrap().status(), Status::NotFound);
+}
+
+#[tokio::test]
+async fn embedded_validators_ranges_and_variants() {
+    let processor = processor();
+    let response = get(&processor, "/static/js/app.js", "If-None-Match: \"3d4e5f\"\r\n").await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::NotModified);
+    let last_modified = response.typed_header::<LastModified>().unwrap();
+    assert!(last_modified.0 > std::time::UNIX_EPOCH);
+
+    let mut response = get(&processor, "/static/js/app.js", "Range: bytes=0-6\r\n").await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::PartialContent);
+    assert_eq!(response.get_header(headers::CONTENT_RANGE).unwrap(), &format!("bytes 0-6/{}", APP.len()));
+    assert_eq!(body(&mut response).await, b"console");
+
+    let mut response = get(&processor, "/static/js/app.js", "Accept-Encoding: br\r\n").await.unwrap();
+    assert_eq!(response.get_header(headers::CONTENT_ENCODING).unwrap(), "br");
+    assert_eq!(response.get_header(headers::CONTENT_TYPE).unwrap(), "text/javascript; charset=utf-8");
+    assert_eq!(body(&mut response).await, b"brotli bytes");
+}
+
+#[tokio::test]
+async fn lists_embedded_directories() {
+    let processor = processor();
+    let mut response = get(&processor, "/static/js/?format=json", "").await.unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&body(&mut response).await).unwrap();
+    let names: Vec<&str> = entries.iter().map(|entry| entry["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["vendor", "app.js", "app.js.br"]);
+    assert_eq!(entries[1]["size"], APP.len());
+}
+
+#[test]
+fn generates_source_and_variants() {
+    let root = std::env::temp_dir().join(format!("yaw-embed-{}", std::process::id()));
+    let site = root.join("site");
+    fs::create_dir_all(site.join("css")).unwrap();
+    let stylesheet = "body { color: black; }\n".repeat(100);
+    fs::write(site.join("css/site.css"), &stylesheet).unwrap();
+    fs::write(site.join("logo.png"), [0x89, b'P', b'N', b'G']).unwrap();
+    fs::write(site.join("tiny.txt"), "x").unwrap();
+
+    let output = root.join("out/site.rs");
+    fs::create_dir_all(output.parent().unwrap()).unwrap();
+    Embed::new(&site).precompress(&[ContentCoding::Gzip]).write(&output).unwrap();
+    let source = fs::read_to_string(&output).unwrap();
+    let paths: Vec<&str> = source
+        .lines()
+        .filter_map(|line| line.split("path: \"").nth(1)?.split('"').next())
+        .collect();
+    // Images aren't worth compressing, and neither are files that would grow.
+    assert_eq!(paths, ["/css/site.css", "/css/site.css.gz", "/logo.png", "/tiny.txt"]);
+    assert!(source.starts_with("&[\n    yaw::embed::EmbeddedFile { path: \"/css/site.css\", content: include_bytes!("));
+    assert!(source.contains("content_type: \"text/css; charset=utf-8\""));
+    assert!(source.contains("content_type: \"image/png\""));
+
+    let variant = fs::read(root.join("out/site/css/site.css.gz")).unwrap();
+    let mut decoded = String::new();
+    std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&variant[..]), &mut decoded).unwrap();
+    assert_eq!(decoded, stylesheet);
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,165 @@
+use std::{error::Error, io};
+
+use crate::http::{
+    errors::{ClientError, ErrorTemplates, HttpError, HttpErrorKind, ServerError},
+    request::Request,
+    response::{Response, Status},
+    typed_headers::{Accept, TypedHeader},
+    ParseError,
+};
+
+#[test]
+fn kinds_map_to_statuses() {
+    assert_eq!(HttpError::from(ClientError::Forbidden).status(), Status::Forbidden);
+    assert_eq!(HttpError::from(ClientError::UriTooLong).status(), Status::UriTooLong);
+    assert_eq!(HttpError::from(ServerError::MethodNotImplemented).status(), Status::NotImplemented);
+    assert_eq!(
+        HttpError::from(ServerError::ServiceUnavailable).kind(),
+        &HttpErrorKind::ServerError(ServerError::ServiceUnavailable)
+    );
+}
+
+#[test]
+fn parse_errors_are_bad_requests_with_a_message() {
+    let error = HttpError::from(ParseError("invalid chunk size"));
+    assert_eq!(error.status(), Status::BadRequest);
+    assert_eq!(error.message(), Some("invalid chunk size"));
+    assert_eq!(error.to_string(), "400 Bad Request: invalid chunk size");
+}
+
+#[test]
+fn display_includes_message_and_source_chain() {
+    let error = HttpError::from(ServerError::InternalServerError)
+        .with_message("unable to read root/secret.txt")
+        .with_source(io::Error::other("disk on fire"));
+    assert_eq!(
+        error.to_string(),
+        "500 Internal Server Error: unable to read root/secret.txt: disk on fire"
+    );
+    assert_eq!(error.source().unwrap().to_string(), "disk on fire");
+}
+
+#[test]
+fn response_keeps_details_private() {
+    let response = HttpError::from(ServerError::InternalServerError)
+        .with_message("unable to read root/secret.txt")
+        .with_source(io::Error::other("disk on fire"))
+        .to_response();
+    let written = response.to_string();
+    assert!(written.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+    assert!(!written.contains("secret"));
+    assert!(!written.contains("disk on fire"));
+}
+
+#[test]
+fn extra_headers_are_sent() {
+    let response = HttpError::from(ServerError::ServiceUnavailable)
+        .with_header("Retry-After", "120")
+        .with_header("Bad Header", "dropped")
+        .to_response();
+    assert_eq!(response.get_header("retry-after"), Some(&"120".to_string()));
+    assert_eq!(response.get_header("Bad Header"), None);
+}
+
+#[test]
+fn unauthorized_has_a_default_challenge() {
+    let response = ClientError::Unauthorized.to_response();
+    assert_eq!(
+        response.get_header("WWW-Authenticate"),
+        Some(&"Basic realm=\"WallyWorld\"".to_string())
+    );
+    let response = HttpError::from(ClientError::Unauthorized)
+        .with_header("WWW-Authenticate", "Bearer")
+        .to_response();
+    assert_eq!(response.get_header("WWW-Authenticate"), Some(&"Bearer".to_string()));
+}
+
+fn negotiate(error: HttpError, accept: &str) -> Response {
+    let accept = Accept::parse(accept).ok();
+    error.to_negotiated_response(accept.as_ref(), &ErrorTemplates::default())
+}
+
+#[test]
+fn browsers_get_html() {
+    let error = HttpError::from(ClientError::BadRequest).with_detail("bad <input>");
+    let response = negotiate(error, "text/html,application/xhtml+xml,*/*;q=0.8");
+    assert_eq!(response.get_header("Content-Type"), Some(&"text/html; charset=utf-8".to_string()));
+    let written = response.to_string();
+    assert!(written.contains("<h1>400 Bad Request</h1>"));
+    assert!(written.contains("bad &lt;input&gt;"));
+}
+
+#[test]
+fn api_clients_get_problem_details() {
+    let error = HttpError::from(ClientError::NotFound).with_detail("no such widget");
+    let response = negotiate(error, "application/json");
+    assert_eq!(response.get_header("Content-Type"), Some(&"application/problem+json".to_string()));
+    let written = response.to_string();
+    let body = &written[written.find("\r\n\r\n").unwrap() + 4..];
+    let problem: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(problem["status"], 404);
+    assert_eq!(problem["title"], "Not Found");
+    assert_eq!(problem["detail"], "no such widget");
+}
+
+#[test]
+fn server_error_details_are_not_rendered() {
+    let error = HttpError::from(ServerError::InternalServerError).with_message("db password wrong");
+    let response = negotiate(error, "application/problem+json");
+    let written = response.to_string();
+    assert!(!written.contains("password"));
+    assert!(!written.contains("detail"));
+}
+
+#[test]
+fn client_error_messages_are_not_rendered() {
+    for accept in ["text/html", "application/json", "text/plain"] {
+        let error = HttpError::from(ClientError::NotFound).with_message("root/private/notes.txt is not a file");
+        assert!(!negotiate(error, accept).to_string().contains("notes.txt"), "{}", accept);
+    }
+}
+
+#[test]
+fn parse_errors_tell_the_client_what_was_malformed() {
+    let response = negotiate(HttpError::from(ParseError("invalid chunk size")), "application/json");
+    assert!(response.to_string().contains("\"detail\":\"invalid chunk size\""));
+}
+
+#[test]
+fn wildcard_accept_gets_problem_details() {
+    for accept in ["*/*", "text/*, application/*;q=0.9"] {
+        let response = negotiate(HttpError::from(ClientError::NotFound), accept);
+        assert_ne!(response.get_header("Content-Type"), Some(&"text/html; charset=utf-8".to_string()), "{}", accept);
+    }
+    let response = negotiate(HttpError::from(ClientError::NotFound), "*/*");
+    assert_eq!(response.get_header("Content-Type"), Some(&"application/problem+json".to_string()));
+    // Naming HTML is enough, even next to a wildcard of the same quality.
+    let response = negotiate(HttpError::from(ClientError::NotFound), "text/html, */*");
+    assert_eq!(response.get_header("Content-Type"), Some(&"text/html; charset=utf-8".to_string()));
+}
+
+#[tokio::test]
+async fn handlers_negotiate_with_their_request() {
+    let raw = "GET / HTTP/1.1\r\nHost: localhost\r\nAccept: application/json\r\n\r\n";
+    let request = Request::load(&mut raw.as_bytes()).await.unwrap();
+    let response = HttpError::from(ClientError::NotFound).to_response_for(&request, &ErrorTemplates::default());
+    assert_eq!(response.get_header("Content-Type"), Some(&"application/problem+json".to_string()));
+}
+
+#[test]
+fn plain_text_without_accept() {
+    let response = HttpError::from(ClientError::NotFound).to_response();
+    assert_eq!(response.get_header("Content-Type"), Some(&"text/plain; charset=utf-8".to_string()));
+    assert!(response.to_string().ends_with("\r\n\r\n404 Not Found\n"));
+}
+
+#[test]
+fn per_status_html_templates() {
+    let mut templates = ErrorTemplates::default();
+    templates.html_by_status.insert(404, "<p>missing: {detail}</p>".to_string());
+    let accept = Accept::parse("text/html").ok();
+    let response = HttpError::from(ClientError::NotFound)
+        .with_detail("x")
+        .to_negotiated_response(accept.as_ref(), &templates);
+    assert!(response.to_string().ends_with("<p>missing: x</p>"));
+}
@@ -0,0 +1,281 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    config::Config,
+    file_cache::{CacheStats, CachedSource, FileCache, FileCacheOptions, Invalidation},
+    file_source::{FileSource, FsSource, SourceFile, SourceMetadata},
+    http::{autoindex::Entry, response::Status},
+    hub::Hub,
+    request_processor::RequestProcessor,
+    tests::common::{body, get, Site},
+};
+
+/// A site whose files are served through a file cache.
+fn cached_site(name: &str, files: &[(&str, &[u8])], options: FileCacheOptions) -> Site {
+    let mut config = Config::default();
+    config.enable_file_cache(options);
+    // Missing siblings would be cached too; `missing_files_are_cached` covers them.
+    config.compression.precompressed.clear();
+    Site::with_config(&format!("cache-{}", name), files, config)
+}
+
+fn cache_stats(site: &Site) -> CacheStats {
+    site.config.file_cache.as_ref().unwrap().stats()
+}
+
+fn mtime_checks() -> FileCacheOptions {
+    FileCacheOptions {
+        invalidation: Invalidation::Mtime {
+            interval: Duration::ZERO,
+        },
+        ..FileCacheOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn hits_and_mtime_invalidation() {
+    let site = cached_site("mtime", &[("page.html", b"first")], mtime_checks());
+    assert_eq!(site.content("/page.html").await, b"first");
+    assert_eq!(site.content("/page.html").await, b"first");
+    let stats = cache_stats(&site);
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+    assert_eq!(stats.bytes, site.root.join("page.html").as_os_str().len() as u64 + 5);
+
+    fs::write(site.root.join("page.html"), "second version").unwrap();
+    assert_eq!(site.content("/page.html").await, b"second version");
+    let stats = cache_stats(&site);
+    assert_eq!((stats.hits, stats.misses, stats.invalidations), (1, 2, 1));
+
+    fs::remove_file(site.root.join("page.html")).unwrap();
+    assert_eq!(
+        site.get("/page.html", "").await.err().unwrap().status(),
+        Status::NotFound
+    );
+}
+
+#[tokio::test]
+async fn cached_files_keep_validators_and_ranges() {
+    let site = cached_site("validators", &[("app.js", b"console.log(1);")], mtime_checks());
+    let etag = site
+        .get("/app.js", "")
+        .await
+        .unwrap()
+        .get_header("ETag")
+        .unwrap()
+        .clone();
+    let response = site
+        .get("/app.js", &format!("If-None-Match: {}\r\n", etag))
+        .await
+        .unwrap();
+    assert_eq!(response.get_status_code(), &Status::NotModified);
+    let mut response = site.get("/app.js", "Range: bytes=0-6\r\n").await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::PartialContent);
+    assert_eq!(body(&mut response).await, b"console");
+    assert_eq!(cache_stats(&site).hits, 2);
+}
+
+#[tokio::test]
+async fn evicts_least_recently_used() {
+    // Entries count their path as well as their content.
+    let root_length = Site::root("cache-lru").join("a.txt").as_os_str().len() as u64;
+    let options = FileCacheOptions {
+        // Room for two of the files below, but not three.
+        max_bytes: 2 * (root_length + 100) + 50,
+        max_file_size: 150,
+        ..mtime_checks()
+    };
+    let (a, b, c, large) = ([b'a'; 100], [b'b'; 100], [b'c'; 100], [b'l'; 200]);
+    let site = cached_site(
+        "lru",
+        &[("a.txt", &a), ("b.txt", &b), ("c.txt", &c), ("large.txt", &large)],
+        options,
+    );
+    site.content("/a.txt").await;
+    site.content("/b.txt").await;
+    site.content("/a.txt").await;
+    site.content("/c.txt").await;
+    let stats = cache_stats(&site);
+    assert_eq!((stats.hits, stats.misses, stats.evictions, stats.entries), (1, 3, 1, 2));
+
+    // b was used least recently, so it went.
+    site.content("/a.txt").await;
+    site.content("/c.txt").await;
+    site.content("/b.txt").await;
+    assert_eq!((cache_stats(&site).hits, cache_stats(&site).misses), (3, 4));
+
+    // The contents of files over the size limit are never kept, only their metadata.
+    assert_eq!(site.content("/large.txt").await, large);
+    assert_eq!(site.content("/large.txt").await, large);
+    let stats = cache_stats(&site);
+    assert_eq!((stats.misses, stats.entries), (6, 3));
+    assert!(stats.bytes < 2 * (root_length + 100) + root_length + 100);
+}
+
+#[tokio::test]
+async fn shared_by_processors() {
+    let site = cached_site("shared", &[("page.html", b"shared")], mtime_checks());
+    site.content("/page.html").await;
+    let other = RequestProcessor::new(site.config.clone(), Hub::new(1));
+    get(&other, "/page.html", "").await.unwrap();
+    assert_eq!((cache_stats(&site).hits, cache_stats(&site).misses), (1, 1));
+}
+
+#[tokio::test]
+async fn notify_invalidation() {
+    let site = cached_site("notify", &[("page.html", b"before")], FileCacheOptions::default());
+    assert_eq!(site.content("/page.html").await, b"before");
+    fs::write(site.root.join("page.html"), "after the change").unwrap();
+    // The watcher reports changes from its own thread, shortly after they happen.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while cache_stats(&site).entries > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(site.content("/page.html").await, b"after the change");
+    // One write can raise several events, some of them after the new content was cached.
+    assert!(cache_stats(&site).invalidations >= 1);
+}
+
+/// Replaces a file with new content of the same length the first time it is
+/// opened, after the descriptor for the old content has been handed out, as
+/// an editor saving it at just the wrong moment would.
+struct RacingSource {
+    inner: FsSource,
+    raced: AtomicBool,
+}
+
+impl FileSource for RacingSource {
+    fn metadata(&self, path: &str) -> io::Result<SourceMetadata> {
+        self.inner.metadata(path)
+    }
+
+    fn open(&self, path: &str) -> io::Result<(SourceFile, SourceMetadata)> {
+        let opened = self.inner.open(path)?;
+        if !self.raced.swap(true, Ordering::SeqCst) {
+            let file_path = self.inner.file_path(path).unwrap();
+            let replacement = file_path.with_extension("new");
+            fs::write(&replacement, b"after!").unwrap();
+            let modified = SystemTime::now() + Duration::from_secs(60);
+            fs::File::options()
+                .write(true)
+                .open(&replacement)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+            fs::rename(&replacement, &file_path).unwrap();
+        }
+        Ok(opened)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        self.inner.read_dir(path)
+    }
+
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        self.inner.file_path(path)
+    }
+}
+
+#[tokio::test]
+async fn changes_before_the_watch_are_not_cached() {
+    let root = Site::root("cache-race");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("page.html"), b"before").unwrap();
+    let source = RacingSource {
+        inner: FsSource::new(&root.display().to_string()),
+        raced: AtomicBool::new(false),
+    };
+    let cache = FileCache::new(FileCacheOptions::default());
+    let cached = CachedSource::new(&source, Some(&cache));
+    let content = |file: SourceFile| match file {
+        SourceFile::Shared(content) => content.to_vec(),
+        _ => panic!("expected the content in memory"),
+    };
+    // The first request gets the file as it was when opened, but it isn't kept.
+    assert_eq!(content(cached.open("/page.html").unwrap().0), b"before");
+    assert_eq!(cache.stats().entries, 0);
+    assert_eq!(content(cached.open("/page.html").unwrap().0), b"after!");
+    assert_eq!(content(cached.open("/page.html").unwrap().0), b"after!");
+    assert_eq!(cache.stats().hits, 1);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn relative_roots_are_watched() {
+    // Relative to the working directory, which tests run in, as the default root is.
+    let root = format!("target/yaw-cache-relative-{}", std::process::id());
+    fs::create_dir_all(&root).unwrap();
+    fs::write(format!("{}/page.html", root), b"before").unwrap();
+    let source = FsSource::new(&root);
+    let cache = FileCache::new(FileCacheOptions::default());
+    let cached = CachedSource::new(&source, Some(&cache));
+    let content = || match cached.open("/page.html").unwrap().0 {
+        SourceFile::Shared(content) => content.to_vec(),
+        _ => panic!("expected the content in memory"),
+    };
+    assert_eq!(content(), b"before");
+    fs::write(format!("{}/page.html", root), b"after the change").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while cache.stats().entries > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(content(), b"after the change");
+    assert!(cache.stats().invalidations >= 1);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+/// Counts the metadata lookups that reach the file system.
+struct CountingSource {
+    inner: FsSource,
+    lookups: AtomicU64,
+}
+
+impl FileSource for CountingSource {
+    fn metadata(&self, path: &str) -> io::Result<SourceMetadata> {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        self.inner.metadata(path)
+    }
+
+    fn open(&self, path: &str) -> io::Result<(SourceFile, SourceMetadata)> {
+        self.inner.open(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<Entry>> {
+        self.inner.read_dir(path)
+    }
+
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        self.inner.file_path(path)
+    }
+}
+
+#[tokio::test]
+async fn missing_files_are_cached() {
+    let root = Site::root("cache-missing");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("app.js"), b"app").unwrap();
+    let source = CountingSource {
+        inner: FsSource::new(&root.display().to_string()),
+        lookups: AtomicU64::new(0),
+    };
+    let cache = FileCache::new(FileCacheOptions::default());
+    let cached = CachedSource::new(&source, Some(&cache));
+    for _ in 0..3 {
+        assert!(cached.metadata("/app.js").is_ok());
+        assert_eq!(cached.metadata("/app.js.br").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+    assert_eq!(source.lookups.load(Ordering::SeqCst), 2);
+
+    // Creating the file invalidates its negative entry.
+    fs::write(root.join("app.js.br"), b"compressed").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while cached.metadata("/app.js.br").is_err() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(cached.metadata("/app.js.br").unwrap().len, 10);
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,92 @@
+use crate::http::{
+    headers::{self, Headers, InvalidHeader},
+    response::Response,
+};
+
+#[test]
+fn lookup_is_case_insensitive() {
+    let mut headers = Headers::new();
+    headers.parse_and_add_header_from("Authorization: Basic abc\r\n".to_string()).ok();
+    assert_eq!(headers.get("authorization"), Some(&"Basic abc".to_string()));
+    assert_eq!(headers.get("AUTHORIZATION"), Some(&"Basic abc".to_string()));
+    assert!(headers.contains("Authorization"));
+}
+
+#[test]
+fn repeated_headers_are_kept_in_order() {
+    let mut headers = Headers::new();
+    headers.append("Accept", "text/html");
+    headers.append("Set-Cookie", "a=1");
+    headers.append("set-cookie", "b=2");
+    let cookies: Vec<&String> = headers.get_all("Set-Cookie").collect();
+    assert_eq!(cookies, vec!["a=1", "b=2"]);
+    assert_eq!(headers.get("set-cookie"), Some(&"a=1".to_string()));
+    assert_eq!(headers.to_string(), "Accept: text/html\r\nSet-Cookie: a=1\r\nset-cookie: b=2\r\n");
+}
+
+#[test]
+fn insert_replaces_in_place() {
+    let mut headers = Headers::new();
+    headers.append("Server", "yaw");
+    headers.append("X-Value", "1");
+    headers.append("Content-Length", "0");
+    headers.append("x-value", "2");
+    headers.insert("X-VALUE", "3");
+    assert_eq!(headers.len(), 3);
+    assert_eq!(headers.to_string(), "Server: yaw\r\nX-Value: 3\r\nContent-Length: 0\r\n");
+}
+
+#[test]
+fn remove_returns_all_values() {
+    let mut headers = Headers::new();
+    headers.append("Vary", "Accept");
+    headers.append("Server", "yaw");
+    headers.append("vary", "Accept-Encoding");
+    assert_eq!(headers.remove("VARY"), vec!["Accept", "Accept-Encoding"]);
+    assert_eq!(headers.len(), 1);
+    assert!(headers.remove("Vary").is_empty());
+}
+
+#[test]
+fn crlf_in_values_is_rejected() {
+    let mut headers = Headers::new();
+    assert_eq!(
+        headers.try_insert("Location", "/home\r\nSet-Cookie: session=evil"),
+        Err(InvalidHeader::Value("Location".to_string()))
+    );
+    assert!(headers.try_append("X-Value", "a\0b").is_err());
+    assert!(headers.try_append("X-Value", "tab\tis fine").is_ok());
+    assert_eq!(headers.len(), 1);
+}
+
+#[test]
+fn header_names_must_be_tokens() {
+    assert!(headers::validate("X-Good_Name", "1").is_ok());
+    assert_eq!(
+        headers::validate("Bad Name", "1"),
+        Err(InvalidHeader::Name("Bad Name".to_string()))
+    );
+    assert!(headers::validate("Bad:Name", "1").is_err());
+    assert!(headers::validate("", "1").is_err());
+}
+
+#[test]
+fn response_drops_injected_headers() {
+    let mut response = Response::new();
+    response.add_header("Location", "/next?q=1\r\n\r\n<html>");
+    assert_eq!(response.get_header("Location"), None);
+    assert!(!response.to_string().contains("<html>"));
+    assert!(response.try_add_header("Location", "/next").is_ok());
+    assert_eq!(response.get_header("location"), Some(&"/next".to_string()));
+}
+
+#[test]
+#[allow(deprecated)]
+fn old_method_names_still_work() {
+    let mut headers = Headers::new();
+    headers.append("X-Value", "1");
+    headers.append("x-value", "2");
+    headers.add_header("X-VALUE", "3");
+    assert_eq!(headers.get_header("x-value"), Some(&"3".to_string()));
+    assert_eq!(headers.len(), 1);
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::{
+    http::websocket::Message,
+    hub::{Hub, Lagged},
+};
+
+use super::{
+    start_server,
+    websocket::{frame, read_frame, FIN, HANDSHAKE, TEXT},
+    HUB,
+};
+
+fn text(text: &str) -> Message {
+    Message::Text(text.to_string())
+}
+
+#[tokio::test]
+async fn broadcast_reaches_every_subscriber() {
+    let hub = Hub::new(8);
+    let mut first = hub.subscribe("news");
+    let mut second = hub.subscribe("news");
+    let mut other = hub.subscribe("sport");
+    assert_eq!(hub.subscriber_count("news"), 2);
+    assert_eq!(hub.subscriber_counts().get("sport"), Some(&1));
+
+    assert_eq!(hub.broadcast("news", text("hello")), 2);
+    assert_eq!(first.recv().await, Ok(text("hello")));
+    assert_eq!(second.recv().await, Ok(text("hello")));
+    assert!(tokio::time::timeout(Duration::from_millis(20), other.recv()).await.is_err());
+}
+
+#[test]
+fn channels_close_with_their_last_subscriber() {
+    let hub = Hub::new(8);
+    let first = hub.subscribe("news");
+    let second = hub.subscribe("news");
+    drop(first);
+    assert_eq!(hub.subscriber_count("news"), 1);
+    drop(second);
+    assert_eq!(hub.subscriber_count("news"), 0);
+    assert!(hub.subscriber_counts().is_empty());
+    assert_eq!(hub.broadcast("news", text("nobody listening")), 0);
+}
+
+#[tokio::test]
+async fn slow_subscribers_miss_the_oldest_messages() {
+    let hub = Hub::new(2);
+    let mut slow = hub.subscribe("ticks");
+    let mut fast = hub.subscribe("ticks");
+    for tick in 1..=5 {
+        // Never waits for the slow subscriber.
+        assert_eq!(hub.broadcast("ticks", text(&tick.to_string())), 2);
+        assert_eq!(fast.recv().await, Ok(text(&tick.to_string())));
+    }
+    assert_eq!(slow.recv().await, Err(Lagged(3)));
+    assert_eq!(slow.recv().await, Ok(text("4")));
+    assert_eq!(slow.recv().await, Ok(text("5")));
+}
+
+async fn join_chat() -> TcpStream {
+    let mut stream = TcpStream::connect("127.0.0.1:8080").await.unwrap();
+    let handshake = HANDSHAKE.replace("GET /echo", "GET /chat?room=hub-test");
+    stream.write_all(handshake.as_bytes()).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    assert!(head.starts_with(b"HTTP/1.1 101 "));
+    stream
+}
+
+#[tokio::test]
+async fn chat_route() {
+    start_server();
+    let mut alice = join_chat().await;
+    let mut bob = join_chat().await;
+    let hub = HUB.get().unwrap();
+    while hub.subscriber_count("hub-test") < 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    alice.write_all(&frame(FIN | TEXT, b"hi bob")).await.unwrap();
+    assert_eq!(read_frame(&mut alice).await.unwrap(), (FIN | TEXT, b"hi bob".to_vec()));
+    assert_eq!(read_frame(&mut bob).await.unwrap(), (FIN | TEXT, b"hi bob".to_vec()));
+
+    drop(alice);
+    while hub.subscriber_count("hub-test") > 1 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
@@ -0,0 +1,195 @@
+use crate::http::{
+    request::{Method, Request, RequestLimits},
+    response::Status,
+};
+
+async fn load_with(raw: &str, limits: &RequestLimits) -> Result<Request, Status> {
+    let mut stream = raw.as_bytes();
+    Request::load_with_limits(&mut stream, limits)
+        .await
+        .map_err(|http_error| *http_error.to_response().get_status_code())
+}
+
+/// Loads `raw` with the strict parser, which the conformance cases below are for.
+async fn load(raw: &str) -> Result<Request, Status> {
+    load_with(raw, &strict()).await
+}
+
+fn strict() -> RequestLimits {
+    RequestLimits {
+        strict: true,
+        ..RequestLimits::default()
+    }
+}
+
+#[tokio::test]
+async fn accepts_well_formed_request() {
+    let request = load("GET /hello?name=yaw HTTP/1.1\r\nHost: localhost:8080\r\nAccept: */*\r\n\r\n")
+        .await
+        .ok()
+        .unwrap();
+    assert!(request.get_method() == &Method::GET);
+    assert_eq!(request.get_path(), "/hello");
+    assert_eq!(request.get_param("name"), Some(&"yaw".to_string()));
+    assert_eq!(request.get_http_version(), "HTTP/1.1");
+    assert_eq!(request.get_header("accept"), Some(&"*/*".to_string()));
+}
+
+#[tokio::test]
+async fn ignores_one_leading_empty_line() {
+    assert!(load("\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n").await.is_ok());
+    assert_eq!(
+        load("\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n").await.err(),
+        Some(Status::BadRequest)
+    );
+}
+
+#[tokio::test]
+async fn accepts_absolute_form() {
+    let request = load("GET http://localhost:8080/hello?name=a HTTP/1.1\r\nHost: localhost:8080\r\n\r\n")
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(request.get_path(), "/hello");
+    assert_eq!(request.get_param("name"), Some(&"a".to_string()));
+}
+
+#[tokio::test]
+async fn rejects_malformed_request_lines() {
+    for raw in [
+        "GET  / HTTP/1.1\r\nHost: a\r\n\r\n",
+        "GET / HTTP/1.1 extra\r\nHost: a\r\n\r\n",
+        "GET\t/ HTTP/1.1\r\nHost: a\r\n\r\n",
+        "GET / HTTP/1.1 \r\nHost: a\r\n\r\n",
+        "GET / HTTP/11\r\nHost: a\r\n\r\n",
+        "GET / http/1.1\r\nHost: a\r\n\r\n",
+        "GET / FOO\r\nHost: a\r\n\r\n",
+        "GET index.html HTTP/1.1\r\nHost: a\r\n\r\n",
+        "GET ftp://a/ HTTP/1.1\r\nHost: a\r\n\r\n",
+        "GET /\x01 HTTP/1.1\r\nHost: a\r\n\r\n",
+        "GE(T / HTTP/1.1\r\nHost: a\r\n\r\n",
+    ] {
+        assert_eq!(load(raw).await.err(), Some(Status::BadRequest), "{:?}", raw);
+    }
+}
+
+#[tokio::test]
+async fn rejects_malformed_header_fields() {
+    for raw in [
+        "GET / HTTP/1.1\r\nHost : a\r\n\r\n",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-Value\t: 1\r\n\r\n",
+        "GET / HTTP/1.1\r\nHost: a\r\nNo colon\r\n\r\n",
+        "GET / HTTP/1.1\r\nHost: a\r\n: empty name\r\n\r\n",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-Value: a\x00b\r\n\r\n",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-Value: a\rb\r\n\r\n",
+        "GET / HTTP/1.1\r\nHost: a\r\nX-Value: 1\r\n continued\r\n\r\n",
+    ] {
+        assert_eq!(load(raw).await.err(), Some(Status::BadRequest), "{:?}", raw);
+    }
+}
+
+#[tokio::test]
+async fn requires_crlf_in_strict_mode() {
+    assert_eq!(
+        load("GET / HTTP/1.1\nHost: a\n\n").await.err(),
+        Some(Status::BadRequest)
+    );
+    assert!(load_with("GET / HTTP/1.1\nHost: a\n\n", &RequestLimits::default()).await.is_ok());
+}
+
+#[tokio::test]
+async fn requires_single_host_for_http_1_1() {
+    assert_eq!(load("GET / HTTP/1.1\r\n\r\n").await.err(), Some(Status::BadRequest));
+    assert_eq!(
+        load("GET / HTTP/1.1\r\nHost: a\r\nHost: b\r\n\r\n").await.err(),
+        Some(Status::BadRequest)
+    );
+    assert_eq!(
+        load("GET / HTTP/1.1\r\nHost: a b\r\n\r\n").await.err(),
+        Some(Status::BadRequest)
+    );
+    assert!(load("GET / HTTP/1.0\r\n\r\n").await.is_ok());
+}
+
+#[tokio::test]
+async fn rejects_truncated_heads() {
+    assert_eq!(load("").await.err(), Some(Status::BadRequest));
+    assert_eq!(load("GET / HTTP/1.1\r\nHost: a\r\n").await.err(), Some(Status::BadRequest));
+    assert_eq!(load("GET / HTTP/1.1").await.err(), Some(Status::BadRequest));
+}
+
+#[tokio::test]
+async fn long_request_line_is_414() {
+    let limits = RequestLimits {
+        max_request_line_length: 32,
+        ..strict()
+    };
+    let long_target = format!("GET /{} HTTP/1.1\r\nHost: a\r\n\r\n", "a".repeat(64));
+    assert_eq!(load_with(&long_target, &limits).await.err(), Some(Status::UriTooLong));
+    assert!(load_with("GET /short HTTP/1.1\r\nHost: a\r\n\r\n", &limits).await.is_ok());
+}
+
+#[tokio::test]
+async fn oversized_header_field_is_431() {
+    let limits = RequestLimits {
+        max_header_length: 32,
+        ..strict()
+    };
+    let raw = format!("GET / HTTP/1.1\r\nHost: a\r\nX-Big: {}\r\n\r\n", "b".repeat(64));
+    assert_eq!(load_with(&raw, &limits).await.err(), Some(Status::RequestHeaderFieldsTooLarge));
+}
+
+#[tokio::test]
+async fn too_many_header_fields_is_431() {
+    let limits = RequestLimits {
+        max_header_count: 3,
+        ..strict()
+    };
+    let raw = "GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\n\r\n";
+    assert!(load_with(raw, &limits).await.is_ok());
+    let raw = "GET / HTTP/1.1\r\nHost: a\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+    assert_eq!(load_with(raw, &limits).await.err(), Some(Status::RequestHeaderFieldsTooLarge));
+}
+
+#[tokio::test]
+async fn oversized_head_is_431() {
+    let limits = RequestLimits {
+        max_head_size: 128,
+        ..strict()
+    };
+    let mut raw = "GET / HTTP/1.1\r\nHost: a\r\n".to_string();
+    for index in 0..10 {
+        raw += &format!("X-Header-{}: {}\r\n", index, "v".repeat(10));
+    }
+    raw += "\r\n";
+    assert_eq!(load_with(&raw, &limits).await.err(), Some(Status::RequestHeaderFieldsTooLarge));
+}
+
+#[tokio::test]
+async fn endless_header_section_is_cut_off() {
+    let mut raw = "GET / HTTP/1.1\r\nHost: a\r\n".to_string();
+    raw += &"X-Header: value\r\n".repeat(100_000);
+    assert_eq!(load(&raw).await.err(), Some(Status::RequestHeaderFieldsTooLarge));
+}
+
+#[tokio::test]
+async fn decodes_percent_escapes_in_the_path() {
+    let request = load("GET /a%20b/%CE%BA%c3%b3.txt?q=a%20b HTTP/1.1\r\nHost: a\r\n\r\n")
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(request.get_path(), "/a b/κó.txt");
+    assert_eq!(request.get_query(), Some("q=a%20b"));
+}
+
+#[tokio::test]
+async fn rejects_paths_that_decode_to_something_unsafe() {
+    for path in [
+        "/a%2Fb", "/a%2fb", "/a%00b", "/../secret", "/docs/../index.html", "/%2e%2e/secret", "/a/%2E%2E",
+        "/%ff.txt", "/%", "/%2", "/%zz",
+    ] {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: a\r\n\r\n", path);
+        assert_eq!(load(&raw).await.err(), Some(Status::BadRequest), "{}", path);
+        assert_eq!(load_with(&raw, &RequestLimits::default()).await.err(), Some(Status::BadRequest), "{}", path);
+    }
+}
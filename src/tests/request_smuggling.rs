@@ -0,0 +1,125 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+use crate::http::{
+    request::{Request, RequestLimits},
+    response::Status,
+};
+
+use super::start_server;
+
+async fn load(raw: &str) -> Result<Request, Status> {
+    let mut stream = raw.as_bytes();
+    Request::load_with_limits(&mut stream, &RequestLimits::default())
+        .await
+        .map_err(|http_error| *http_error.to_response().get_status_code())
+}
+
+#[tokio::test]
+async fn reads_content_length_body() {
+    let request = load("POST /echo HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello")
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(request.get_body(), b"hello");
+}
+
+#[tokio::test]
+async fn reads_chunked_body() {
+    let raw = "POST /echo HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n\
+               5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\n";
+    let request = load(raw).await.ok().unwrap();
+    assert_eq!(request.get_body(), b"hello world");
+}
+
+#[tokio::test]
+async fn rejects_content_length_with_transfer_encoding() {
+    let raw = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::BadRequest));
+}
+
+#[tokio::test]
+async fn rejects_duplicate_content_length() {
+    for raw in [
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nContent-Length: 3\r\n\r\nabc",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd",
+        "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3, 4\r\n\r\nabcd",
+    ] {
+        assert_eq!(load(raw).await.err(), Some(Status::BadRequest), "{:?}", raw);
+    }
+}
+
+#[tokio::test]
+async fn rejects_malformed_content_length() {
+    for content_length in ["+3", "-3", "0x3", "3 3", "", "abc"] {
+        let raw = format!("POST / HTTP/1.1\r\nHost: a\r\nContent-Length: {}\r\n\r\nabc", content_length);
+        assert_eq!(load(&raw).await.err(), Some(Status::BadRequest), "{:?}", content_length);
+    }
+}
+
+#[tokio::test]
+async fn rejects_unsupported_transfer_encodings() {
+    let raw = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, identity\r\n\r\n0\r\n\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::BadRequest));
+    let raw = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::NotImplemented));
+    let raw = "POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::BadRequest));
+}
+
+#[tokio::test]
+async fn rejects_invalid_chunk_sizes() {
+    for chunk in [
+        "0x5\r\nhello\r\n0\r\n\r\n",
+        " 5\r\nhello\r\n0\r\n\r\n",
+        "-5\r\nhello\r\n0\r\n\r\n",
+        "5 \r\nhello\r\n0\r\n\r\n",
+        "g\r\nhello\r\n0\r\n\r\n",
+        "\r\nhello\r\n0\r\n\r\n",
+        "ffffffffffffffffff\r\nhello\r\n0\r\n\r\n",
+        "5\nhello\r\n0\r\n\r\n",
+        "5\r\nhelloX\r\n0\r\n\r\n",
+        "5\r\nhello\r\n0\r\n",
+    ] {
+        let raw = format!("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n{}", chunk);
+        assert_eq!(load(&raw).await.err(), Some(Status::BadRequest), "{:?}", chunk);
+    }
+}
+
+#[tokio::test]
+async fn rejects_obs_fold_even_when_lenient() {
+    let limits = RequestLimits {
+        strict: false,
+        ..RequestLimits::default()
+    };
+    let raw = "GET / HTTP/1.1\r\nHost: a\r\nX-Folded: a\r\n b: c\r\n\r\n";
+    let mut stream = raw.as_bytes();
+    let result = Request::load_with_limits(&mut stream, &limits).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn rejects_oversized_bodies() {
+    let raw = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 99999999999\r\n\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::ContentTooLarge));
+    let raw = "POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 999999999999999999999999\r\n\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::ContentTooLarge));
+    let raw = "POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nfffffffff\r\n";
+    assert_eq!(load(raw).await.err(), Some(Status::ContentTooLarge));
+}
+
+#[test]
+fn framing_error_closes_connection() {
+    start_server();
+    let mut stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+    stream
+        .write_all(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nGET /admin HTTP/1.1\r\nHost: a\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    assert!(response.contains("Connection: close\r\n"));
+    assert_eq!(response.matches("HTTP/1.1").count(), 1);
+}
@@ -0,0 +1,138 @@
+use std::{
+    io::{self, Write},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::stream;
+use tokio::{
+    io::{AsyncReadExt, AsyncWrite},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::http::{body::ResponseBody, response::Response, sendfile};
+
+/// Accepts at most three bytes per write, like a congested socket.
+struct TrickleWriter {
+    written: Vec<u8>,
+}
+
+impl AsyncWrite for TrickleWriter {
+    fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let length = buf.len().min(3);
+        self.written.extend_from_slice(&buf[..length]);
+        Poll::Ready(Ok(length))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+async fn send(response: Response, client_version: &str) -> String {
+    let mut writer = TrickleWriter { written: Vec::new() };
+    response.send_to(&mut writer, client_version).await.unwrap();
+    String::from_utf8(writer.written).unwrap()
+}
+
+#[tokio::test]
+async fn partial_writes_are_completed() {
+    let body = "x".repeat(10_000);
+    let written = send(Response::text(body.clone()), "HTTP/1.1").await;
+    assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(written.contains("Content-Length: 10000\r\n"));
+    assert!(written.ends_with(&format!("\r\n\r\n{}", body)));
+}
+
+#[tokio::test]
+async fn file_bodies_are_streamed() {
+    let path = std::env::temp_dir().join(format!("yaw-body-{}.txt", std::process::id()));
+    std::fs::File::create(&path).unwrap().write_all(b"from a file").unwrap();
+    let file = tokio::fs::File::open(&path).await.unwrap();
+    let mut response = Response::new();
+    response.set_body_file(file, 11);
+    assert_eq!(response.get_body().len(), Some(11));
+    let written = send(response, "HTTP/1.1").await;
+    std::fs::remove_file(&path).unwrap();
+    assert!(written.contains("Content-Length: 11\r\n"));
+    assert!(written.ends_with("\r\n\r\nfrom a file"));
+}
+
+#[tokio::test]
+async fn streams_of_unknown_length_are_chunked() {
+    let chunks = vec![Ok(b"hello".to_vec()), Ok(Vec::new()), Ok(b" world!".to_vec())];
+    let mut response = Response::new();
+    response.set_body_stream(stream::iter(chunks));
+    let written = send(response, "HTTP/1.1").await;
+    assert!(!written.contains("Content-Length"));
+    assert!(written.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(written.ends_with("\r\n\r\n5\r\nhello\r\n7\r\n world!\r\n0\r\n\r\n"));
+}
+
+#[tokio::test]
+async fn http_1_0_streams_are_delimited_by_close() {
+    let mut response = Response::new();
+    response.set_body_stream(stream::iter(vec![Ok(b"hello".to_vec())]));
+    let written = send(response, "HTTP/1.0").await;
+    assert!(!written.contains("Transfer-Encoding"));
+    assert!(written.contains("Connection: close\r\n"));
+    assert!(written.ends_with("\r\n\r\nhello"));
+}
+
+#[tokio::test]
+async fn stream_errors_abort_the_response() {
+    let chunks = vec![Ok(b"hello".to_vec()), Err(io::Error::other("producer failed"))];
+    let mut response = Response::new();
+    response.set_body_stream(stream::iter(chunks));
+    let mut writer = TrickleWriter { written: Vec::new() };
+    assert!(response.send_to(&mut writer, "HTTP/1.1").await.is_err());
+    assert!(!String::from_utf8(writer.written).unwrap().ends_with("0\r\n\r\n"));
+}
+
+/// Sends `response` over a loopback connection with `send_to_tcp` and
+/// returns everything the client received.
+async fn send_over_tcp(response: Response) -> Vec<u8> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let mut received = Vec::new();
+        TcpStream::connect(address).await.unwrap().read_to_end(&mut received).await.unwrap();
+        received
+    });
+    let (mut stream, _) = listener.accept().await.unwrap();
+    response.send_to_tcp(&mut stream, "HTTP/1.1").await.unwrap();
+    drop(stream);
+    client.await.unwrap()
+}
+
+#[tokio::test]
+async fn large_files_are_sent_over_tcp() {
+    let path = std::env::temp_dir().join(format!("yaw-sendfile-{}.bin", std::process::id()));
+    let content: Vec<u8> = (0..3 * sendfile::MIN_SIZE).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &content).unwrap();
+
+    for (offset, length) in [(0, content.len() as u64), (1000, sendfile::MIN_SIZE + 1), (0, 10)] {
+        let mut response = Response::new();
+        response.set_response_body(ResponseBody::File {
+            file: tokio::fs::File::open(&path).await.unwrap(),
+            offset,
+            length,
+        });
+        let received = send_over_tcp(response).await;
+        let head = format!("Content-Length: {}\r\n", length);
+        let body_start = received.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+        assert!(String::from_utf8_lossy(&received[..body_start]).contains(&head));
+        assert_eq!(&received[body_start..], &content[offset as usize..][..length as usize]);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn other_bodies_are_sent_over_tcp() {
+    let received = send_over_tcp(Response::text("plain")).await;
+    assert!(received.ends_with(b"\r\n\r\nplain"));
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::http::response::{Redirect, Response, Status};
+
+#[derive(Serialize)]
+struct Build {
+    id: u32,
+    status: &'static str,
+}
+
+fn body(response: &Response) -> String {
+    let written = response.to_string();
+    written[written.find("\r\n\r\n").unwrap() + 4..].to_string()
+}
+
+#[test]
+fn json_sets_content_type_and_length() {
+    let response = Response::json(&Build { id: 7, status: "passed" }).unwrap();
+    assert_eq!(response.get_status_code(), &Status::Ok);
+    assert_eq!(response.get_header("Content-Type"), Some(&"application/json".to_string()));
+    assert_eq!(body(&response), r#"{"id":7,"status":"passed"}"#);
+    assert_eq!(response.get_header("Content-Length"), Some(&"26".to_string()));
+}
+
+#[test]
+fn json_serialization_errors_are_server_errors() {
+    let mut map = HashMap::new();
+    map.insert((1, 2), "tuple keys are not valid JSON object keys");
+    let error = Response::json(&map).err().unwrap();
+    assert_eq!(error.status(), Status::InternalServerError);
+}
+
+#[test]
+fn html_and_text() {
+    let response = Response::html("<p>hi</p>");
+    assert_eq!(response.get_header("content-type"), Some(&"text/html; charset=utf-8".to_string()));
+    assert_eq!(body(&response), "<p>hi</p>");
+    let response = Response::text("hi");
+    assert_eq!(response.get_header("content-type"), Some(&"text/plain; charset=utf-8".to_string()));
+    assert_eq!(response.get_header("content-length"), Some(&"2".to_string()));
+}
+
+#[test]
+fn redirects() {
+    for (kind, code) in [
+        (Redirect::MovedPermanently, 301),
+        (Redirect::Found, 302),
+        (Redirect::SeeOther, 303),
+        (Redirect::TemporaryRedirect, 307),
+        (Redirect::PermanentRedirect, 308),
+    ] {
+        let response = Response::redirect("/next", kind).unwrap();
+        assert_eq!(response.get_status_code().as_u16(), code);
+        assert_eq!(response.get_header("Location"), Some(&"/next".to_string()));
+    }
+    assert!(Response::redirect("/next\r\nSet-Cookie: a=b", Redirect::Found).is_err());
+}
+
+#[test]
+fn no_content_has_no_length() {
+    let response = Response::no_content();
+    assert_eq!(response.get_status_code(), &Status::NoContent);
+    assert_eq!(response.get_header("Content-Length"), None);
+    assert!(response.to_string().ends_with("\r\n\r\n"));
+}
+
+#[test]
+fn builder() {
+    let response = Response::builder()
+        .status(Status::Created)
+        .header("Location", "/builds/7")
+        .append_header("Set-Cookie", "a=1")
+        .append_header("Set-Cookie", "b=2")
+        .json(&Build { id: 7, status: "queued" })
+        .build()
+        .unwrap();
+    assert_eq!(response.get_status_code(), &Status::Created);
+    assert_eq!(response.get_headers().get_all("set-cookie").count(), 2);
+    assert!(body(&response).contains("queued"));
+
+    let error = Response::builder()
+        .header("X Bad", "1")
+        .text("ignored")
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(error.status(), Status::InternalServerError);
+}
@@ -0,0 +1,93 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use futures::StreamExt;
+
+use crate::http::{
+    request::Request,
+    sse::{Disconnected, Event, Sse},
+};
+
+use super::start_server;
+
+async fn request(raw: &str) -> Request {
+    let mut stream = raw.as_bytes();
+    Request::load(&mut stream).await.unwrap()
+}
+
+#[test]
+fn event_fields() {
+    let event = Event::new("line one\nline two")
+        .event("status")
+        .id("42")
+        .retry(Duration::from_secs(3));
+    assert_eq!(
+        event.format(),
+        "event: status\nid: 42\nretry: 3000\ndata: line one\ndata: line two\n\n"
+    );
+}
+
+#[test]
+fn fields_cannot_inject_events() {
+    let event = Event::new("ok").event("a\n\ndata: injected").id("1\r\n2");
+    assert_eq!(event.format(), "event: adata: injected\nid: 12\ndata: ok\n\n");
+}
+
+#[tokio::test]
+async fn last_event_id_is_available_to_the_producer() {
+    let request = request("GET /events HTTP/1.1\r\nHost: a\r\nLast-Event-ID: 7\r\n\r\n").await;
+    let (_sse, events) = Sse::channel(&request, Duration::from_secs(15));
+    assert_eq!(events.last_event_id(), Some("7"));
+}
+
+#[tokio::test]
+async fn idle_streams_send_keep_alive_comments() {
+    let request = request("GET /events HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    let (sse, events) = Sse::channel(&request, Duration::from_millis(20));
+    let mut stream = Box::pin(sse.into_stream());
+    assert_eq!(stream.next().await.unwrap().unwrap(), b": keep-alive\n\n");
+    events.send(Event::new("hi")).await.unwrap();
+    assert_eq!(stream.next().await.unwrap().unwrap(), b"data: hi\n\n");
+    drop(events);
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn producer_stops_when_the_client_disconnects() {
+    let request = request("GET /events HTTP/1.1\r\nHost: a\r\n\r\n").await;
+    let (sse, events) = Sse::channel(&request, Duration::from_secs(15));
+    let producer = tokio::spawn(async move {
+        let mut sent = 0;
+        while events.send(Event::new("tick")).await.is_ok() {
+            sent += 1;
+        }
+        sent
+    });
+    let mut stream = Box::pin(sse.into_stream());
+    stream.next().await.unwrap().unwrap();
+    drop(stream);
+    let sent = tokio::time::timeout(Duration::from_secs(1), producer).await.unwrap().unwrap();
+    assert!(sent < 100);
+    let (_, events) = Sse::channel(&request, Duration::from_secs(15));
+    assert_eq!(events.send(Event::new("late")).await, Err(Disconnected));
+}
+
+#[test]
+fn sse_route() {
+    start_server();
+    let mut stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+    stream
+        .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\nLast-Event-ID: 4\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Content-Type: text/event-stream\r\n"));
+    assert!(response.contains("Transfer-Encoding: chunked\r\n"));
+    assert!(response.contains("id: 5\ndata: build 5\n\n"));
+    assert!(response.contains("id: 7\ndata: build 7\n\n"));
+    assert!(response.ends_with("0\r\n\r\n"));
+}
@@ -0,0 +1,580 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    config::{Config, TrailingSlash},
+    http::{
+        autoindex::AutoIndexOptions,
+        caching::CacheRule,
+        conditional::ETagKind,
+        errors::{ErrorTemplates, HttpError},
+        headers,
+        response::{Response, Status},
+        typed_headers::{Accept, CacheControl, EntityTag, Expires, LastModified, TypedHeader},
+    },
+    mount::{HiddenFiles, MountOptions},
+    tests::common::{body, Site},
+};
+
+const SCRIPT: &[u8] = b"console.log('hello');\n";
+
+#[tokio::test]
+async fn serves_precompressed_siblings() {
+    let site = Site::new(
+        "precompressed",
+        &[("app.js", SCRIPT), ("app.js.br", b"brotli bytes"), ("app.js.gz", b"gzip bytes")],
+    );
+    for (accept_encoding, coding, content) in [
+        ("gzip, br", Some("br"), &b"brotli bytes"[..]),
+        ("br;q=0.5, gzip", Some("gzip"), b"gzip bytes"),
+        ("deflate", None, SCRIPT),
+        ("", None, SCRIPT),
+    ] {
+        let mut response = site
+            .get("/app.js", &format!("Accept-Encoding: {}\r\n", accept_encoding))
+            .await
+            .unwrap();
+        assert_eq!(response.get_header(headers::CONTENT_ENCODING).map(String::as_str), coding);
+        assert_eq!(response.get_header(headers::CONTENT_TYPE).unwrap(), "text/javascript; charset=utf-8");
+        assert_eq!(response.get_header(headers::VARY).unwrap(), "Accept-Encoding");
+        assert_eq!(response.get_header(headers::CONTENT_LENGTH).unwrap(), &content.len().to_string());
+        assert_eq!(body(&mut response).await, content);
+    }
+}
+
+#[tokio::test]
+async fn files_without_siblings_do_not_vary() {
+    let site = Site::new("no-siblings", &[("app.js", SCRIPT)]);
+    let response = site.get("/app.js", "Accept-Encoding: br, gzip\r\n").await.unwrap();
+    assert!(response.get_header(headers::CONTENT_ENCODING).is_none());
+    assert!(response.get_header(headers::VARY).is_none());
+}
+
+/// A date in the past and one in the future, relative to any test file.
+const LONG_AGO: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+const FAR_FUTURE: &str = "Fri, 01 Jan 2100 00:00:00 GMT";
+
+async fn status(site: &Site, extra_headers: &str) -> Status {
+    match site.get("/app.js", extra_headers).await {
+        Ok(response) => *response.get_status_code(),
+        Err(e) => e.status(),
+    }
+}
+
+#[tokio::test]
+async fn files_have_validators() {
+    let site = Site::new("validators", &[("app.js", SCRIPT)]);
+    let response = site.get("/app.js", "").await.unwrap();
+    let etag = response.typed_header::<EntityTag>().unwrap();
+    assert!(!etag.weak);
+    assert!(response.typed_header::<LastModified>().is_some());
+
+    let config = Config {
+        file_etags: ETagKind::Weak,
+        ..Config::default()
+    };
+    let site = Site::with_config("weak-validators", &[("app.js", SCRIPT)], config);
+    assert!(site.get("/app.js", "").await.unwrap().typed_header::<EntityTag>().unwrap().weak);
+}
+
+#[tokio::test]
+async fn if_none_match_and_if_modified_since() {
+    let site = Site::new("if-none-match", &[("app.js", SCRIPT)]);
+    let etag = site.get("/app.js", "").await.unwrap().typed_header::<EntityTag>().unwrap();
+
+    let mut response = site.get("/app.js", &format!("If-None-Match: \"other\", {}\r\n", etag)).await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::NotModified);
+    assert!(response.get_header(headers::CONTENT_LENGTH).is_none());
+    assert_eq!(response.typed_header::<EntityTag>(), Some(etag.clone()));
+    assert!(body(&mut response).await.is_empty());
+    // If-None-Match uses the weak comparison.
+    let weak = EntityTag::weak(&etag.tag);
+    assert_eq!(status(&site, &format!("If-None-Match: {}\r\n", weak)).await, Status::NotModified);
+    assert_eq!(status(&site, "If-None-Match: *\r\n").await, Status::NotModified);
+    assert_eq!(status(&site, "If-None-Match: \"other\"\r\n").await, Status::Ok);
+
+    assert_eq!(status(&site, &format!("If-Modified-Since: {}\r\n", FAR_FUTURE)).await, Status::NotModified);
+    assert_eq!(status(&site, &format!("If-Modified-Since: {}\r\n", LONG_AGO)).await, Status::Ok);
+    assert_eq!(status(&site, "If-Modified-Since: yesterday\r\n").await, Status::Ok);
+    // If-Modified-Since is ignored when If-None-Match is present.
+    let both = format!("If-None-Match: \"other\"\r\nIf-Modified-Since: {}\r\n", FAR_FUTURE);
+    assert_eq!(status(&site, &both).await, Status::Ok);
+}
+
+#[tokio::test]
+async fn if_match_and_if_unmodified_since() {
+    let site = Site::new("if-match", &[("app.js", SCRIPT)]);
+    let etag = site.get("/app.js", "").await.unwrap().typed_header::<EntityTag>().unwrap();
+
+    assert_eq!(status(&site, &format!("If-Match: {}\r\n", etag)).await, Status::Ok);
+    assert_eq!(status(&site, "If-Match: *\r\n").await, Status::Ok);
+    assert_eq!(status(&site, "If-Match: \"other\"\r\n").await, Status::PreconditionFailed);
+    // If-Match uses the strong comparison.
+    let weak = EntityTag::weak(&etag.tag);
+    assert_eq!(status(&site, &format!("If-Match: {}\r\n", weak)).await, Status::PreconditionFailed);
+
+    assert_eq!(status(&site, &format!("If-Unmodified-Since: {}\r\n", FAR_FUTURE)).await, Status::Ok);
+    assert_eq!(
+        status(&site, &format!("If-Unmodified-Since: {}\r\n", LONG_AGO)).await,
+        Status::PreconditionFailed
+    );
+    // If-Unmodified-Since is ignored when If-Match is present.
+    let both = format!("If-Match: {}\r\nIf-Unmodified-Since: {}\r\n", etag, LONG_AGO);
+    assert_eq!(status(&site, &both).await, Status::Ok);
+    // If-Match is evaluated before If-None-Match.
+    let both = format!("If-Match: \"other\"\r\nIf-None-Match: {}\r\n", etag);
+    assert_eq!(status(&site, &both).await, Status::PreconditionFailed);
+}
+
+const DIGITS: &[u8] = b"0123456789abcdefghij";
+
+async fn range(site: &Site, extra_headers: &str) -> Response {
+    site.get("/digits.txt", extra_headers).await.unwrap()
+}
+
+#[tokio::test]
+async fn single_ranges() {
+    let site = Site::new("single-range", &[("digits.txt", DIGITS)]);
+    for (header, content_range, content) in [
+        ("bytes=0-4", "bytes 0-4/20", &b"01234"[..]),
+        ("bytes=15-", "bytes 15-19/20", b"fghij"),
+        ("bytes=-3", "bytes 17-19/20", b"hij"),
+        ("bytes=18-100", "bytes 18-19/20", b"ij"),
+        // Overlapping and adjacent ranges are merged.
+        ("bytes=0-2, 1-4, 5-6", "bytes 0-6/20", b"0123456"),
+    ] {
+        let mut response = range(&site, &format!("Range: {}\r\n", header)).await;
+        assert_eq!(response.get_status_code(), &Status::PartialContent, "{}", header);
+        assert_eq!(response.get_header(headers::CONTENT_RANGE).unwrap(), content_range);
+        assert_eq!(response.get_header(headers::CONTENT_LENGTH).unwrap(), &content.len().to_string());
+        assert_eq!(body(&mut response).await, content);
+    }
+
+    let mut response = range(&site, "").await;
+    assert_eq!(response.get_status_code(), &Status::Ok);
+    assert_eq!(response.get_header(headers::ACCEPT_RANGES).unwrap(), "bytes");
+    assert_eq!(body(&mut response).await, DIGITS);
+    // Other units and malformed ranges are ignored.
+    assert_eq!(range(&site, "Range: items=0-1\r\n").await.get_status_code(), &Status::Ok);
+    assert_eq!(range(&site, "Range: bytes=5-1\r\n").await.get_status_code(), &Status::Ok);
+}
+
+#[tokio::test]
+async fn multiple_ranges() {
+    let site = Site::new("multiple-ranges", &[("digits.txt", DIGITS)]);
+    let mut response = range(&site, "Range: bytes=10-11, 0-1\r\n").await;
+    assert_eq!(response.get_status_code(), &Status::PartialContent);
+    let content_type = response.get_header(headers::CONTENT_TYPE).unwrap().clone();
+    let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap();
+    let length: usize = response.get_header(headers::CONTENT_LENGTH).unwrap().parse().unwrap();
+    let body = String::from_utf8(body(&mut response).await).unwrap();
+    assert_eq!(body.len(), length);
+    assert_eq!(
+        body,
+        format!(
+            "\r\n--{0}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/20\r\n\r\n01\
+             \r\n--{0}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 10-11/20\r\n\r\nab\
+             \r\n--{0}--\r\n",
+            boundary
+        )
+    );
+}
+
+#[tokio::test]
+async fn unsatisfiable_ranges() {
+    let site = Site::new("unsatisfiable-range", &[("digits.txt", DIGITS)]);
+    let error = site.get("/digits.txt", "Range: bytes=20-, 30-40\r\n").await.err().unwrap();
+    assert_eq!(error.status(), Status::RangeNotSatisfiable);
+    assert_eq!(error.headers().get(headers::CONTENT_RANGE).unwrap(), "bytes */20");
+    // One satisfiable range is enough.
+    let response = range(&site, "Range: bytes=30-40, 19-\r\n").await;
+    assert_eq!(response.get_header(headers::CONTENT_RANGE).unwrap(), "bytes 19-19/20");
+}
+
+#[tokio::test]
+async fn if_range() {
+    let site = Site::new("if-range", &[("digits.txt", DIGITS)]);
+    let response = range(&site, "").await;
+    let etag = response.typed_header::<EntityTag>().unwrap();
+    let last_modified = response.get_header(headers::LAST_MODIFIED).unwrap().clone();
+
+    for (if_range, status) in [
+        (etag.to_string(), Status::PartialContent),
+        (last_modified, Status::PartialContent),
+        ("\"stale\"".to_string(), Status::Ok),
+        (EntityTag::weak(&etag.tag).to_string(), Status::Ok),
+        (LONG_AGO.to_string(), Status::Ok),
+    ] {
+        let response = range(&site, &format!("Range: bytes=0-1\r\nIf-Range: {}\r\n", if_range)).await;
+        assert_eq!(response.get_status_code(), &status, "{}", if_range);
+    }
+}
+
+fn cached_site(name: &str) -> Site {
+    let mut config = Config::default();
+    config.add_cache_rule("/assets/**", CacheControl::new().with("max-age", Some("31536000")).with("immutable", None));
+    config.add_cache_rule("*.html", CacheControl::new().with("no-cache", None));
+    config.add_request_handler("/page.html", |_, response| {
+        response.set_typed_header(&CacheControl::new().with("private", None));
+    });
+    config.add_request_handler("/plain.html", |_, _| {});
+    Site::with_config(
+        name,
+        &[("index.html", b"<p>home</p>"), ("assets/app.js", SCRIPT), ("app.js", SCRIPT)],
+        config,
+    )
+}
+
+#[tokio::test]
+async fn cache_rules() {
+    let site = cached_site("cache-rules");
+    for (path, cache_control) in [
+        ("/assets/app.js", Some("max-age=31536000, immutable")),
+        ("/", Some("no-cache")),
+        ("/index.html", Some("no-cache")),
+        ("/app.js", None),
+        // Handlers may set their own; otherwise the rules apply to them too.
+        ("/page.html", Some("private")),
+        ("/plain.html", Some("no-cache")),
+    ] {
+        let response = site.get(path, "").await.unwrap();
+        assert_eq!(response.get_header(headers::CACHE_CONTROL).map(String::as_str), cache_control, "{}", path);
+        assert!(response.get_header(headers::EXPIRES).is_none());
+    }
+
+    let etag = site.get("/assets/app.js", "").await.unwrap().typed_header::<EntityTag>().unwrap();
+    let response = site.get("/assets/app.js", &format!("If-None-Match: {}\r\n", etag)).await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::NotModified);
+    assert_eq!(response.get_header(headers::CACHE_CONTROL).unwrap(), "max-age=31536000, immutable");
+    assert!(site.get("/assets/missing.js", "").await.is_err());
+}
+
+#[tokio::test]
+async fn expires_for_http_1_0() {
+    let site = cached_site("cache-expires");
+    let response = site.get_with_version("HTTP/1.0", "/assets/app.js", "").await.unwrap();
+    let Expires(expires) = response.typed_header::<Expires>().unwrap();
+    let ahead = expires.duration_since(SystemTime::now()).unwrap();
+    assert!(ahead > Duration::from_secs(364 * 24 * 60 * 60) && ahead <= Duration::from_secs(365 * 24 * 60 * 60));
+
+    let response = site.get_with_version("HTTP/1.0", "/", "").await.unwrap();
+    assert_eq!(response.typed_header::<Expires>(), Some(Expires(UNIX_EPOCH)));
+    let response = site.get_with_version("HTTP/1.0", "/page.html", "").await.unwrap();
+    assert!(response.get_header(headers::EXPIRES).is_none());
+}
+
+fn listed_site(name: &str, config: Config) -> Site {
+    Site::with_config(
+        name,
+        &[
+            ("files/b.txt", b"bb"),
+            ("files/a<b>.txt", b"a"),
+            ("files/large.bin", &[0; 100]),
+            ("files/.secret", b"hidden"),
+            ("files/docs/readme.txt", b"read me"),
+            ("other/c.txt", b"c"),
+        ],
+        config,
+    )
+}
+
+async fn listed_names(site: &Site, path: &str) -> Vec<String> {
+    let mut response = site.get(path, "").await.unwrap();
+    assert_eq!(response.get_header(headers::CONTENT_TYPE).unwrap(), "application/json");
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&body(&mut response).await).unwrap();
+    entries.iter().map(|entry| entry["name"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test]
+async fn autoindex_html() {
+    let mut config = Config::default();
+    config.add_autoindex("/files/");
+    let site = listed_site("autoindex-html", config);
+    let mut response = site.get("/files/", "").await.unwrap();
+    assert_eq!(response.get_header(headers::CONTENT_TYPE).unwrap(), "text/html; charset=utf-8");
+    let html = String::from_utf8(body(&mut response).await).unwrap();
+    assert!(html.contains("<title>Index of /files/</title>"));
+    assert!(html.contains("<a href=\"../\">../</a>"));
+    assert!(html.contains("<a href=\"/files/docs/\">docs/</a>"));
+    assert!(html.contains("<a href=\"/files/a%3Cb%3E.txt\">a&lt;b&gt;.txt</a></td><td>1</td>"));
+    assert!(html.contains("<a href=\"/files/large.bin\">large.bin</a></td><td>100</td>"));
+    assert!(html.contains("<a href=\"?sort=name&amp;order=desc\">Name</a>"));
+    assert!(!html.contains(".secret"));
+    assert!(html.find("docs/").unwrap() < html.find("a&lt;b&gt;.txt").unwrap());
+
+    // Files are still served, and only directories under the prefix are listed.
+    assert_eq!(site.content("/files/b.txt").await, b"bb");
+    assert_eq!(site.get("/files/docs/", "").await.unwrap().get_status_code(), &Status::Ok);
+    assert_eq!(site.get("/other/", "").await.err().unwrap().status(), Status::NotFound);
+    assert_eq!(site.get("/", "").await.err().unwrap().status(), Status::NotFound);
+}
+
+#[tokio::test]
+async fn autoindex_links_resolve() {
+    let mut config = Config::default();
+    config.add_autoindex("/");
+    let names: [&str; 6] = ["a b.txt", "κόσμε.txt", "<tag>.txt", "#hash.txt", "100%.txt", "sub dir/inner.txt"];
+    let files: Vec<(&str, &[u8])> = names.iter().map(|name| (*name, &b"x"[..])).collect();
+    let site = Site::with_config("autoindex-links", &files, config);
+    let html = String::from_utf8(site.content("/").await).unwrap();
+    let hrefs: Vec<&str> = html
+        .split("<a href=\"")
+        .skip(1)
+        .map(|link| &link[..link.find('"').unwrap()])
+        .filter(|href| href.starts_with('/'))
+        .collect();
+    assert_eq!(hrefs.len(), 6, "{}", html);
+    for href in hrefs {
+        let href = href.replace("&amp;", "&");
+        let response = site.get(&href, "").await.unwrap();
+        assert_eq!(response.get_status_code(), &Status::Ok, "{}", href);
+    }
+    assert_eq!(site.content("/sub%20dir/inner.txt").await, b"x");
+    let response = site.get("/sub%20dir?sort=size", "").await.unwrap();
+    assert_eq!(response.get_header(headers::LOCATION).unwrap(), "/sub%20dir/?sort=size");
+}
+
+#[tokio::test]
+async fn autoindex_json_sorting() {
+    let mut config = Config::default();
+    config.add_autoindex("/");
+    let site = listed_site("autoindex-json", config);
+    assert_eq!(listed_names(&site, "/?format=json").await, ["files", "other"]);
+    for (query, names) in [
+        ("format=json", ["docs", "a<b>.txt", "b.txt", "large.bin"]),
+        ("format=json&order=desc", ["docs", "large.bin", "b.txt", "a<b>.txt"]),
+        ("format=json&sort=size&order=desc", ["docs", "large.bin", "b.txt", "a<b>.txt"]),
+        ("format=json&sort=size", ["docs", "a<b>.txt", "b.txt", "large.bin"]),
+    ] {
+        assert_eq!(listed_names(&site, &format!("/files/?{}", query)).await, names, "{}", query);
+    }
+}
+
+#[tokio::test]
+async fn autoindex_hidden_files() {
+    let config = Config {
+        autoindex: AutoIndexOptions {
+            paths: vec!["/files/".to_string()],
+            show_hidden: true,
+        },
+        ..Config::default()
+    };
+    let site = listed_site("autoindex-hidden", config);
+    assert!(listed_names(&site, "/files/?format=json").await.contains(&".secret".to_string()));
+}
+
+#[tokio::test]
+async fn index_files() {
+    let mut config = Config::default();
+    config.set_index_files(&["index.htm", "index.html"]);
+    let site = Site::with_config(
+        "index-files",
+        &[
+            ("index.html", b"home"),
+            ("docs/index.html", b"docs"),
+            ("docs/index.htm", b"old docs"),
+            ("blog/index.html", b"blog"),
+            ("empty/file.txt", b"file"),
+        ],
+        config,
+    );
+    for (path, content) in [("/", &b"home"[..]), ("/docs/", b"old docs"), ("/blog/", b"blog")] {
+        assert_eq!(body(&mut site.get(path, "").await.unwrap()).await, content, "{}", path);
+    }
+    assert_eq!(site.get("/empty/", "").await.err().unwrap().status(), Status::NotFound);
+
+    for (path, location) in [("/blog", "/blog/"), ("/docs?page=2", "/docs/?page=2"), ("/empty", "/empty/")] {
+        let response = site.get(path, "").await.unwrap();
+        assert_eq!(response.get_status_code(), &Status::MovedPermanently, "{}", path);
+        assert_eq!(response.get_header(headers::LOCATION).unwrap(), location);
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn single_index_file() {
+    let mut config = Config::default();
+    assert_eq!(config.index(), "index.html");
+    config.set_index("home.html");
+    assert_eq!(config.index_files, ["home.html"]);
+    assert_eq!(config.index(), "home.html");
+}
+
+async fn routed(trailing_slash: TrailingSlash, path: &str) -> Result<Response, HttpError> {
+    let mut config = Config {
+        trailing_slash,
+        ..Config::default()
+    };
+    config.add_request_handler("/about", |_, response| response.set_body("about".to_string()));
+    config.add_request_handler("/api/", |_, response| response.set_body("api".to_string()));
+    let site = Site::with_config(&format!("trailing-slash-{:?}", trailing_slash), &[], config);
+    site.get(path, "").await
+}
+
+#[tokio::test]
+async fn trailing_slash_on_routes() {
+    for trailing_slash in [TrailingSlash::Exact, TrailingSlash::Redirect, TrailingSlash::Ignore] {
+        assert_eq!(body(&mut routed(trailing_slash, "/about").await.unwrap()).await, b"about");
+        assert_eq!(body(&mut routed(trailing_slash, "/api/").await.unwrap()).await, b"api");
+    }
+    assert!(routed(TrailingSlash::Exact, "/about/").await.is_err());
+    assert!(routed(TrailingSlash::Exact, "/api").await.is_err());
+
+    for (path, location) in [("/about/", "/about"), ("/api?v=1", "/api/?v=1")] {
+        let response = routed(TrailingSlash::Redirect, path).await.unwrap();
+        assert_eq!(response.get_status_code(), &Status::MovedPermanently);
+        assert_eq!(response.get_header(headers::LOCATION).unwrap(), location);
+    }
+
+    assert_eq!(body(&mut routed(TrailingSlash::Ignore, "/about/").await.unwrap()).await, b"about");
+    assert_eq!(body(&mut routed(TrailingSlash::Ignore, "/api").await.unwrap()).await, b"api");
+}
+
+const BROWSER_ACCEPT: &str = "Accept: text/html,application/xhtml+xml,*/*;q=0.8\r\n";
+
+#[tokio::test]
+async fn spa_fallback() {
+    let mut config = Config::default();
+    config.set_spa_fallback("index.html");
+    config.add_cache_rule("*.html", CacheControl::new().with("no-cache", None));
+    let site = Site::with_config(
+        "spa-fallback",
+        &[("index.html", b"<div id=app></div>"), ("app.js", SCRIPT), ("docs/guide.html", b"guide")],
+        config,
+    );
+    for path in ["/", "/users/42", "/settings/profile/", "/docs/missing"] {
+        let mut response = site.get(path, BROWSER_ACCEPT).await.unwrap();
+        assert_eq!(response.get_status_code(), &Status::Ok, "{}", path);
+        assert_eq!(response.get_header(headers::CACHE_CONTROL).unwrap(), "no-cache");
+        assert_eq!(body(&mut response).await, b"<div id=app></div>");
+    }
+    // Existing files and directories are served as usual.
+    assert_eq!(body(&mut site.get("/app.js", BROWSER_ACCEPT).await.unwrap()).await, SCRIPT);
+    assert_eq!(body(&mut site.get("/docs/guide.html", BROWSER_ACCEPT).await.unwrap()).await, b"guide");
+    assert_eq!(site.get("/docs", BROWSER_ACCEPT).await.unwrap().get_status_code(), &Status::MovedPermanently);
+
+    for (path, extra_headers) in [
+        ("/missing.js", BROWSER_ACCEPT),
+        ("/users/avatar.png", BROWSER_ACCEPT),
+        ("/users/42", ""),
+        ("/users/42", "Accept: application/json\r\n"),
+        ("/users/42", "Accept: */*\r\n"),
+        ("/users/42", "Accept: text/html;q=0\r\n"),
+    ] {
+        let error = site.get(path, extra_headers).await.err().unwrap();
+        assert_eq!(error.status(), Status::NotFound, "{} {}", path, extra_headers);
+    }
+}
+
+#[tokio::test]
+async fn no_spa_fallback_by_default() {
+    let site = Site::new("no-spa-fallback", &[("index.html", b"home")]);
+    assert_eq!(site.get("/users/42", BROWSER_ACCEPT).await.err().unwrap().status(), Status::NotFound);
+}
+
+#[tokio::test]
+async fn spa_fallback_has_its_own_headers() {
+    let mut config = Config::default();
+    config.set_spa_fallback("index.html");
+    config.add_cache_rule("/users/**", CacheControl::new().with("max-age", Some("3600")));
+    config.add_cache_rule("*.html", CacheControl::new().with("no-cache", None));
+    let site = Site::with_config("spa-fallback-headers", &[("index.html", b"<div id=app></div>")], config);
+    for version in ["HTTP/1.1", "HTTP/1.0"] {
+        let response = site.get_with_version(version, "/users/42", BROWSER_ACCEPT).await.unwrap();
+        assert_eq!(response.get_header(headers::CACHE_CONTROL).unwrap(), "no-cache", "{}", version);
+        let expires = response.get_header(headers::EXPIRES).map(String::as_str);
+        assert_eq!(expires, (version == "HTTP/1.0").then_some("Thu, 01 Jan 1970 00:00:00 GMT"), "{}", version);
+    }
+}
+
+fn mounted_site(name: &str) -> Site {
+    let root = Site::root(name);
+    let mut config = Config::default();
+    config.add_mount("/assets", &format!("{}/build/assets", root.display()), MountOptions::default());
+    config.add_mount("/assets/vendor/", &format!("{}/vendor", root.display()), MountOptions::default());
+    config.add_mount(
+        "/docs",
+        &format!("{}/site/docs", root.display()),
+        MountOptions {
+            index_files: vec!["README.html".to_string()],
+            autoindex: true,
+            cache_rules: vec![CacheRule::new("*", CacheControl::new().with("max-age", Some("60")))],
+            hidden_files: HiddenFiles::Deny,
+        },
+    );
+    config.add_cache_rule("*.html", CacheControl::new().with("no-cache", None));
+    config.add_request_handler("/assets/status", |_, response| response.set_body("handler".to_string()));
+    config.add_request_handler("/assets/app.js", |_, response| response.set_body("shadowed".to_string()));
+    Site::with_config(
+        name,
+        &[
+            ("index.html", b"home"),
+            ("assets/app.js", b"root app"),
+            ("build/assets/app.js", SCRIPT),
+            ("vendor/lib.js", b"vendor lib"),
+            ("site/docs/README.html", b"docs"),
+            ("site/docs/api/v1.html", b"v1"),
+            ("site/docs/.env", b"SECRET=1"),
+            ("site/docs/.drafts/next.html", b"draft"),
+        ],
+        config,
+    )
+}
+
+#[tokio::test]
+async fn mounts() {
+    let site = mounted_site("mounts");
+    for (path, content) in [
+        ("/", &b"home"[..]),
+        // Files in a mount take precedence over handlers, which serve the rest of it.
+        ("/assets/app.js", SCRIPT),
+        ("/assets/status", b"handler"),
+        ("/assets/vendor/lib.js", b"vendor lib"),
+        ("/docs/", b"docs"),
+        ("/docs/api/v1.html", b"v1"),
+    ] {
+        assert_eq!(body(&mut site.get(path, "").await.unwrap()).await, content, "{}", path);
+    }
+    for path in [
+        "/assets/missing.js", "/assets/lib.js", "/docs/.env", "/docs/.drafts/next.html", "/docs/.%65nv",
+    ] {
+        assert_eq!(site.get(path, "").await.err().unwrap().status(), Status::NotFound, "{}", path);
+    }
+    assert_eq!(site.get("/assetsx/app.js", "").await.err().unwrap().status(), Status::NotFound);
+
+    let response = site.get("/docs", "").await.unwrap();
+    assert_eq!(response.get_status_code(), &Status::MovedPermanently);
+    assert_eq!(response.get_header(headers::LOCATION).unwrap(), "/docs/");
+}
+
+#[tokio::test]
+async fn mount_options() {
+    let site = mounted_site("mount-options");
+    // Mount rules come before the global ones, which still apply elsewhere.
+    for (path, cache_control) in [("/docs/api/v1.html", Some("max-age=60")), ("/", Some("no-cache")), ("/assets/app.js", None)] {
+        let response = site.get(path, "").await.unwrap();
+        assert_eq!(response.get_header(headers::CACHE_CONTROL).map(String::as_str), cache_control, "{}", path);
+    }
+
+    let mut response = site.get("/docs/api/?format=json", "").await.unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(&body(&mut response).await).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["name"], "v1.html");
+    assert_eq!(site.get("/assets/vendor/", "").await.err().unwrap().status(), Status::NotFound);
+}
+
+#[tokio::test]
+async fn not_found_bodies_do_not_echo_paths() {
+    let site = Site::new("not-found-body", &[("empty/file.txt", b"file")]);
+    for path in ["/empty/", "/missing.txt", "/empty/missing.txt"] {
+        let error = site.get(path, "").await.err().unwrap();
+        assert_eq!(error.status(), Status::NotFound, "{}", path);
+        assert!(error.message().is_some(), "{}", path);
+        for accept in ["text/html", "application/json", "text/plain"] {
+            let accept = Accept::parse(accept).ok();
+            let error = site.get(path, "").await.err().unwrap();
+            let written = error.to_negotiated_response(accept.as_ref(), &ErrorTemplates::default()).to_string();
+            assert!(!written.contains(&site.root.display().to_string()), "{} {}", path, written);
+            assert!(!written.contains(path.trim_end_matches('/')), "{} {}", path, written);
+        }
+    }
+}
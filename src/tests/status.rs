@@ -0,0 +1,58 @@
+use crate::http::response::{Response, Status};
+
+#[test]
+fn codes_round_trip() {
+    for code in 100..1000 {
+        let status = Status::from_u16(code).unwrap();
+        assert_eq!(status.as_u16(), code);
+    }
+    assert_eq!(Status::from_u16(99), None);
+    assert_eq!(Status::from_u16(1000), None);
+}
+
+#[test]
+fn registered_codes_have_variants() {
+    assert_eq!(Status::from_u16(201), Some(Status::Created));
+    assert_eq!(Status::from_u16(308), Some(Status::PermanentRedirect));
+    assert_eq!(Status::from_u16(429), Some(Status::TooManyRequests));
+    assert!(matches!(Status::from_u16(599), Some(Status::Custom(code)) if code.get() == 599));
+}
+
+#[test]
+fn custom_codes_come_from_from_u16() {
+    let custom = Status::from_u16(599).unwrap();
+    assert_eq!(custom, Status::from_u16(599).unwrap());
+    assert_ne!(custom, Status::from_u16(598).unwrap());
+    // Registered codes never end up as `Custom`.
+    for code in 100..1000 {
+        let status = Status::from_u16(code).unwrap();
+        assert_eq!(matches!(status, Status::Custom(_)), status.reason_phrase().is_empty(), "{}", code);
+    }
+}
+
+#[test]
+fn standard_reason_phrases() {
+    assert_eq!(Status::Ok.reason_phrase(), "OK");
+    assert_eq!(Status::BadRequest.reason_phrase(), "Bad Request");
+    assert_eq!(Status::ImUsed.reason_phrase(), "IM Used");
+    assert_eq!(Status::from_u16(299).unwrap().reason_phrase(), "");
+    assert_eq!(Status::NotModified.to_string(), "304 Not Modified");
+}
+
+#[test]
+fn classes() {
+    assert!(Status::Continue.is_informational());
+    assert!(Status::NoContent.is_success());
+    assert!(Status::SeeOther.is_redirection());
+    assert!(Status::Conflict.is_client_error());
+    assert!(Status::from_u16(599).unwrap().is_server_error());
+}
+
+#[test]
+fn status_line_uses_code_and_phrase() {
+    let mut response = Response::new();
+    response.set_status_code(Status::PartialContent);
+    assert!(response.to_string().starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    response.set_status_code(Status::from_u16(299).unwrap());
+    assert!(response.to_string().starts_with("HTTP/1.1 299 \r\n"));
+}
@@ -0,0 +1,158 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::http::{
+    headers::Headers,
+    typed_headers::{
+        Accept, AcceptEncoding, Authorization, ByteRange, CacheControl, ContentRange, ContentType, Date, EntityTag, Host,
+        IfMatch, IfNoneMatch, IfRange, Range, TypedHeader,
+    },
+};
+
+#[test]
+fn content_type() {
+    let content_type = ContentType::parse("Text/HTML; Charset=\"utf-8\"").ok().unwrap();
+    assert_eq!(content_type.mime_type(), "text/html");
+    assert_eq!(content_type.charset(), Some("utf-8"));
+    assert_eq!(content_type.format(), "text/html; charset=utf-8");
+    assert!(ContentType::parse("text").is_err());
+}
+
+#[test]
+fn accept_orders_by_quality() {
+    let accept = Accept::parse("text/*;q=0.5, application/json, */*;q=0.1").ok().unwrap();
+    assert_eq!(accept.media_ranges()[0].mime_type, "application/json");
+    assert_eq!(accept.quality("text/plain"), 0.5);
+    assert_eq!(accept.quality("image/png"), 0.1);
+    assert_eq!(accept.preferred(&["text/html", "application/json"]), Some("application/json"));
+    assert!(Accept::parse("text/html;q=1.5").is_err());
+}
+
+#[test]
+fn accept_zero_quality_excludes() {
+    let accept = Accept::parse("text/html;q=0, */*").ok().unwrap();
+    assert_eq!(accept.quality("text/html"), 0.0);
+    assert_eq!(accept.preferred(&["text/html"]), None);
+}
+
+#[test]
+fn accept_encoding() {
+    let accept_encoding = AcceptEncoding::parse("GZIP;q=0.8, br, *;q=0.1, identity;q=0").ok().unwrap();
+    assert_eq!(accept_encoding.quality("gzip"), 0.8);
+    assert_eq!(accept_encoding.quality("deflate"), 0.1);
+    assert_eq!(accept_encoding.quality("identity"), 0.0);
+    assert_eq!(accept_encoding.preferred(&["gzip", "br"]), Some("br"));
+
+    let empty = AcceptEncoding::parse("").ok().unwrap();
+    assert_eq!(empty.quality("identity"), 1.0);
+    assert_eq!(empty.preferred(&["br", "gzip"]), None);
+    assert!(AcceptEncoding::parse("gzip;q=2").is_err());
+}
+
+#[test]
+fn cache_control() {
+    let cache_control = CacheControl::parse("public, max-age=60, no-transform").ok().unwrap();
+    assert_eq!(cache_control.max_age(), Some(60));
+    assert!(cache_control.no_transform());
+    assert!(!cache_control.no_store());
+    let built = CacheControl::new().with("max-age", Some("31536000")).with("immutable", None);
+    assert_eq!(built.format(), "max-age=31536000, immutable");
+}
+
+#[test]
+fn range() {
+    let range = Range::parse("bytes=0-99, 200-, -50").ok().unwrap();
+    assert_eq!(
+        range.ranges(),
+        &[ByteRange::FromTo(0, 99), ByteRange::From(200), ByteRange::Last(50)]
+    );
+    assert_eq!(ByteRange::FromTo(0, 999).resolve(100), Some((0, 99)));
+    assert_eq!(ByteRange::Last(500).resolve(100), Some((0, 99)));
+    assert_eq!(ByteRange::From(100).resolve(100), None);
+    assert!(Range::parse("bytes=5-1").is_err());
+    assert!(Range::parse("items=0-1").is_err());
+}
+
+#[test]
+fn content_range() {
+    let range = ContentRange::Bytes {
+        first: 0,
+        last: 499,
+        length: Some(1234),
+    };
+    assert_eq!(range.format(), "bytes 0-499/1234");
+    assert_eq!(ContentRange::parse("bytes 0-499/1234").ok(), Some(range));
+    assert_eq!(ContentRange::parse("bytes */1234").ok(), Some(ContentRange::Unsatisfied(1234)));
+    assert!(ContentRange::parse("bytes 0-499/*").is_ok());
+    assert!(ContentRange::parse("bytes 10-5/20").is_err());
+    assert!(ContentRange::parse("bytes 0-20/20").is_err());
+}
+
+#[test]
+fn if_range() {
+    assert_eq!(IfRange::parse("\"v1\"").ok(), Some(IfRange::ETag(EntityTag::strong("v1"))));
+    assert_eq!(
+        IfRange::parse("Sun, 06 Nov 1994 08:49:37 GMT").ok(),
+        Some(IfRange::Date(UNIX_EPOCH + Duration::from_secs(784111777)))
+    );
+    assert!(IfRange::parse("soon").is_err());
+}
+
+#[test]
+fn if_none_match() {
+    let if_none_match = IfNoneMatch::parse("\"a\", W/\"b\"").ok().unwrap();
+    assert!(if_none_match.matches(&EntityTag::strong("b")));
+    assert!(!if_none_match.matches(&EntityTag::strong("c")));
+    assert_eq!(IfNoneMatch::parse("*").ok(), Some(IfNoneMatch::Any));
+    assert!(!EntityTag::weak("a").strong_eq(&EntityTag::strong("a")));
+}
+
+#[test]
+fn if_match() {
+    let if_match = IfMatch::parse("\"a\", W/\"b\"").ok().unwrap();
+    assert!(if_match.matches(&EntityTag::strong("a")));
+    assert!(!if_match.matches(&EntityTag::strong("b")));
+    assert!(!if_match.matches(&EntityTag::weak("a")));
+    assert_eq!(IfMatch::parse("*").ok().unwrap(), IfMatch::Any);
+    assert!(IfMatch::parse("a").is_err());
+}
+
+#[test]
+fn authorization() {
+    let basic = Authorization::parse("Basic dXNlcjpwYXNzd29yZA==").ok().unwrap();
+    assert_eq!(
+        basic,
+        Authorization::Basic {
+            username: "user".to_string(),
+            password: "password".to_string()
+        }
+    );
+    assert_eq!(basic.format(), "Basic dXNlcjpwYXNzd29yZA==");
+    assert_eq!(Authorization::parse("Bearer abc").ok(), Some(Authorization::Bearer("abc".to_string())));
+}
+
+#[test]
+fn host() {
+    let host = Host::parse("example.com:8080").ok().unwrap();
+    assert_eq!((host.host.as_str(), host.port), ("example.com", Some(8080)));
+    let host = Host::parse("[::1]").ok().unwrap();
+    assert_eq!((host.host.as_str(), host.port), ("[::1]", None));
+    assert!(Host::parse("example.com:http").is_err());
+}
+
+#[test]
+fn date() {
+    let date = Date::parse("Sun, 06 Nov 1994 08:49:37 GMT").ok().unwrap();
+    assert_eq!(date, Date(UNIX_EPOCH + Duration::from_secs(784111777)));
+    assert_eq!(date.format(), "Sun, 06 Nov 1994 08:49:37 GMT");
+}
+
+#[test]
+fn typed_lookup_joins_repeated_fields() {
+    let mut headers = Headers::new();
+    headers.append("accept", "text/html;q=0.2");
+    headers.append("Accept", "application/json");
+    let accept: Accept = headers.typed().unwrap();
+    assert_eq!(accept.media_ranges().len(), 2);
+    headers.insert_typed(&CacheControl::new().with("no-cache", None));
+    assert_eq!(headers.get("cache-control"), Some(&"no-cache".to_string()));
+}
@@ -0,0 +1,425 @@
+//! Frame-level cases modelled on the Autobahn test suite, run against an echo
+//! handler over an in-memory connection.
+
+use tokio::{
+    io::{duplex, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream},
+    net::TcpStream,
+};
+
+use crate::http::{
+    headers,
+    request::{Request, RequestLimits},
+    response::Status,
+    websocket::{self, close_code, CloseFrame, Message, WebSocket, WebSocketLimits},
+};
+
+use super::start_server;
+
+pub(super) const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+pub(super) const FIN: u8 = 0x80;
+
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+const MAX_MESSAGE_SIZE: usize = 128 * 1024;
+
+pub(super) const HANDSHAKE: &str = "GET /echo HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: keep-alive, Upgrade\r\n\
+                         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+
+/// A masked client frame.
+pub(super) fn frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [0x37, 0xfa, 0x21, 0x3d];
+    let mut frame = vec![first_byte];
+    match payload.len() {
+        length if length < 126 => frame.push(0x80 | length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(index, byte)| byte ^ mask[index % 4]));
+    frame
+}
+
+fn close(code: u16, reason: &[u8]) -> Vec<u8> {
+    let mut payload = code.to_be_bytes().to_vec();
+    payload.extend_from_slice(reason);
+    frame(FIN | CLOSE, &payload)
+}
+
+/// Reads a server frame, or `None` once the server has closed the connection.
+pub(super) async fn read_frame<R: AsyncRead + Unpin>(client: &mut R) -> Option<(u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    client.read_exact(&mut header).await.ok()?;
+    assert_eq!(header[1] & 0x80, 0, "server frames must not be masked");
+    let length = match header[1] {
+        126 => client.read_u16().await.unwrap() as usize,
+        127 => client.read_u64().await.unwrap() as usize,
+        length => length as usize,
+    };
+    let mut payload = vec![0; length];
+    client.read_exact(&mut payload).await.unwrap();
+    Some((header[0], payload))
+}
+
+async fn assert_closed_with(client: &mut DuplexStream, code: u16) {
+    let (first_byte, payload) = read_frame(client).await.unwrap();
+    assert_eq!(first_byte, FIN | CLOSE);
+    assert_eq!(payload[..2], code.to_be_bytes());
+    assert!(read_frame(client).await.is_none());
+}
+
+async fn echo(mut socket: WebSocket<DuplexStream>) {
+    while let Some(Ok(message)) = socket.recv().await {
+        if let Message::Text(_) | Message::Binary(_) = message {
+            let _ = socket.send(message).await;
+        }
+    }
+}
+
+fn limits() -> WebSocketLimits {
+    WebSocketLimits {
+        max_frame_size: MAX_FRAME_SIZE,
+        max_message_size: MAX_MESSAGE_SIZE,
+    }
+}
+
+fn echo_server() -> DuplexStream {
+    let (client, server) = duplex(1024 * 1024);
+    tokio::spawn(echo(WebSocket::from_stream(server, limits())));
+    client
+}
+
+async fn request(raw: &str) -> Request {
+    let mut stream = raw.as_bytes();
+    Request::load(&mut stream).await.unwrap()
+}
+
+#[test]
+fn accept_key() {
+    assert_eq!(websocket::accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[tokio::test]
+async fn handshake() {
+    let response = websocket::accept(&request(HANDSHAKE).await).unwrap();
+    assert_eq!(response.get_status_code(), &Status::SwitchingProtocols);
+    assert_eq!(response.get_header(headers::UPGRADE).unwrap(), "websocket");
+    assert_eq!(response.get_header(headers::CONNECTION).unwrap(), "Upgrade");
+    assert_eq!(
+        response.get_header(headers::SEC_WEBSOCKET_ACCEPT).unwrap(),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+    assert!(response.get_header(headers::CONTENT_LENGTH).is_none());
+}
+
+#[tokio::test]
+async fn handshake_errors() {
+    let plain_get = request("GET /echo HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    let error = websocket::accept(&plain_get).err().unwrap();
+    assert_eq!(error.status(), Status::UpgradeRequired);
+    assert_eq!(error.headers().get(headers::UPGRADE).unwrap(), "websocket");
+
+    let old_version = request(&HANDSHAKE.replace("Version: 13", "Version: 8")).await;
+    let error = websocket::accept(&old_version).err().unwrap();
+    assert_eq!(error.status(), Status::UpgradeRequired);
+    assert_eq!(error.headers().get(headers::SEC_WEBSOCKET_VERSION).unwrap(), "13");
+
+    let short_key = request(&HANDSHAKE.replace("dGhlIHNhbXBsZSBub25jZQ==", "c2hvcnQ=")).await;
+    assert_eq!(websocket::accept(&short_key).err().unwrap().status(), Status::BadRequest);
+
+    let http_1_0 = request(&HANDSHAKE.replace("HTTP/1.1", "HTTP/1.0")).await;
+    assert_eq!(websocket::accept(&http_1_0).err().unwrap().status(), Status::BadRequest);
+}
+
+/// Autobahn 1.1 and 1.2: text and binary messages across payload length encodings.
+#[tokio::test]
+async fn echoes_messages() {
+    let mut client = echo_server();
+    for length in [0, 125, 126, 65535, 65536] {
+        let text = "*".repeat(length);
+        client.write_all(&frame(FIN | TEXT, text.as_bytes())).await.unwrap();
+        assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | TEXT, text.into_bytes()));
+
+        let binary = vec![0xfe; length];
+        client.write_all(&frame(FIN | BINARY, &binary)).await.unwrap();
+        assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | BINARY, binary));
+    }
+    client.write_all(&close(close_code::NORMAL, b"")).await.unwrap();
+    assert_closed_with(&mut client, close_code::NORMAL).await;
+}
+
+/// Autobahn 2.x: pings are answered with the same payload; oversized ones fail.
+#[tokio::test]
+async fn ping_pong() {
+    let mut client = echo_server();
+    client.write_all(&frame(FIN | PING, b"Hello")).await.unwrap();
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | PONG, b"Hello".to_vec()));
+    client.write_all(&frame(FIN | PING, &[0xff; 125])).await.unwrap();
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | PONG, vec![0xff; 125]));
+    // Unsolicited pongs are ignored.
+    client.write_all(&frame(FIN | PONG, b"unsolicited")).await.unwrap();
+    client.write_all(&frame(FIN | PING, &[0; 126])).await.unwrap();
+    assert_closed_with(&mut client, close_code::PROTOCOL_ERROR).await;
+}
+
+/// Autobahn 3.x and 4.x: reserved bits and opcodes, and unmasked frames.
+#[tokio::test]
+async fn protocol_errors() {
+    let mut unmasked = frame(FIN | TEXT, b"");
+    unmasked[1] &= 0x7f;
+    unmasked.truncate(2);
+    for bad_frame in [
+        frame(FIN | 0x40 | TEXT, b"rsv1"),
+        frame(FIN | 0x10 | PING, b"rsv3"),
+        frame(FIN | 0x3, b"reserved data opcode"),
+        frame(FIN | 0xB, b"reserved control opcode"),
+        unmasked,
+    ] {
+        let mut client = echo_server();
+        client.write_all(&bad_frame).await.unwrap();
+        assert_closed_with(&mut client, close_code::PROTOCOL_ERROR).await;
+    }
+}
+
+/// Autobahn 5.x: fragments are joined, with control frames allowed in between.
+#[tokio::test]
+async fn fragmentation() {
+    let mut client = echo_server();
+    client.write_all(&frame(TEXT, b"frag")).await.unwrap();
+    client.write_all(&frame(FIN | PING, b"between")).await.unwrap();
+    client.write_all(&frame(0x0, b"men")).await.unwrap();
+    client.write_all(&frame(FIN, b"ted")).await.unwrap();
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | PONG, b"between".to_vec()));
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | TEXT, b"fragmented".to_vec()));
+
+    for bad_sequence in [
+        vec![frame(FIN, b"continuation without a message")],
+        vec![frame(TEXT, b"first"), frame(FIN | TEXT, b"not a continuation")],
+        vec![frame(PING, b"fragmented"), frame(FIN, b" ping")],
+    ] {
+        let mut client = echo_server();
+        for bad_frame in bad_sequence {
+            client.write_all(&bad_frame).await.unwrap();
+        }
+        assert_closed_with(&mut client, close_code::PROTOCOL_ERROR).await;
+    }
+}
+
+/// Autobahn 5.15-5.20: one-byte fragments with pings and unsolicited pongs
+/// between them; pings are answered as they arrive, before the message.
+#[tokio::test]
+async fn fragments_interleaved_with_control_frames() {
+    let mut client = echo_server();
+    let message = b"fragmented binary";
+    for (index, byte) in message.iter().enumerate() {
+        let first_byte = match index {
+            0 => BINARY,
+            index if index == message.len() - 1 => FIN,
+            _ => 0x0,
+        };
+        client.write_all(&frame(first_byte, &[*byte])).await.unwrap();
+        if index % 5 == 0 {
+            client.write_all(&frame(FIN | PING, &[index as u8])).await.unwrap();
+            client.write_all(&frame(FIN | PONG, b"unsolicited")).await.unwrap();
+        }
+    }
+    for index in (0..message.len()).step_by(5) {
+        assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | PONG, vec![index as u8]));
+    }
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | BINARY, message.to_vec()));
+
+    // A close frame in the middle of a message ends the connection without it.
+    let mut client = echo_server();
+    client.write_all(&frame(TEXT, b"never ")).await.unwrap();
+    client.write_all(&close(close_code::NORMAL, b"")).await.unwrap();
+    client.write_all(&frame(FIN, b"finished")).await.unwrap();
+    assert_closed_with(&mut client, close_code::NORMAL).await;
+}
+
+/// Autobahn 6.x: text must be UTF-8, which may only be complete once joined.
+#[tokio::test]
+async fn utf8_validation() {
+    let mut client = echo_server();
+    let text = "κόσμε".as_bytes();
+    client.write_all(&frame(TEXT, &text[..3])).await.unwrap();
+    client.write_all(&frame(FIN, &text[3..])).await.unwrap();
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | TEXT, text.to_vec()));
+
+    // "κόσμε" followed by an encoded UTF-16 surrogate.
+    let invalid = [0xce, 0xba, 0xe1, 0xbd, 0xb9, 0xcf, 0x83, 0xce, 0xbc, 0xce, 0xb5, 0xed, 0xa0, 0x80];
+    client.write_all(&frame(FIN | TEXT, &invalid)).await.unwrap();
+    assert_closed_with(&mut client, close_code::INVALID_PAYLOAD).await;
+}
+
+/// Autobahn 6.2-6.4: UTF-8 split inside code points across one-byte
+/// fragments, and invalid sequences in the middle of a fragmented message.
+#[tokio::test]
+async fn fragmented_utf8() {
+    let mut client = echo_server();
+    let text = "Hello-µ@ßöäüàá-UTF-8!! κόσμε 𝄞".as_bytes();
+    for (index, byte) in text.iter().enumerate() {
+        let first_byte = match index {
+            0 => TEXT,
+            index if index == text.len() - 1 => FIN,
+            _ => 0x0,
+        };
+        client.write_all(&frame(first_byte, &[*byte])).await.unwrap();
+    }
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | TEXT, text.to_vec()));
+
+    for invalid in [&[0xf4, 0x90, 0x80, 0x80][..], &[0xc0, 0xaf], &[0xed, 0xbf, 0xbf], &[0xfe]] {
+        let mut client = echo_server();
+        client.write_all(&frame(TEXT, b"valid start ")).await.unwrap();
+        client.write_all(&frame(0x0, invalid)).await.unwrap();
+        client.write_all(&frame(FIN, b" valid end")).await.unwrap();
+        assert_closed_with(&mut client, close_code::INVALID_PAYLOAD).await;
+    }
+}
+
+/// Autobahn 7.7 and 7.9: every code allowed on the wire is echoed, and
+/// reserved, unassigned or out-of-range codes fail.
+#[tokio::test]
+async fn close_codes() {
+    for code in [1000, 1001, 1002, 1003, 1007, 1008, 1009, 1010, 1011, 3000, 3999, 4000, 4999] {
+        let mut client = echo_server();
+        client.write_all(&close(code, b"")).await.unwrap();
+        assert_closed_with(&mut client, code).await;
+    }
+    for code in [0, 999, 1004, 1005, 1006, 1015, 1016, 1100, 2000, 2999, 5000, 65535] {
+        let mut client = echo_server();
+        client.write_all(&close(code, b"")).await.unwrap();
+        assert_closed_with(&mut client, close_code::PROTOCOL_ERROR).await;
+    }
+}
+
+/// Autobahn 7.x: the close code is echoed, invalid close frames fail.
+#[tokio::test]
+async fn close_handshake() {
+    let mut client = echo_server();
+    client.write_all(&frame(FIN | CLOSE, b"")).await.unwrap();
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | CLOSE, Vec::new()));
+    assert!(read_frame(&mut client).await.is_none());
+
+    let mut client = echo_server();
+    client.write_all(&close(3000, b"bye")).await.unwrap();
+    // Frames after the close frame are ignored.
+    client.write_all(&frame(FIN | TEXT, b"late")).await.unwrap();
+    assert_closed_with(&mut client, 3000).await;
+
+    for (bad_close, code) in [
+        (frame(FIN | CLOSE, &[0x03]), close_code::PROTOCOL_ERROR),
+        (close(1005, b""), close_code::PROTOCOL_ERROR),
+        (close(999, b""), close_code::PROTOCOL_ERROR),
+        (close(2000, b""), close_code::PROTOCOL_ERROR),
+        (close(close_code::NORMAL, &[0xff, 0xfe]), close_code::INVALID_PAYLOAD),
+    ] {
+        let mut client = echo_server();
+        client.write_all(&bad_close).await.unwrap();
+        assert_closed_with(&mut client, code).await;
+    }
+}
+
+/// Autobahn 9.x, scaled down: frames and joined messages over the limits fail.
+#[tokio::test]
+async fn size_limits() {
+    let mut client = echo_server();
+    let largest = vec![0; MAX_FRAME_SIZE];
+    client.write_all(&frame(FIN | BINARY, &largest)).await.unwrap();
+    assert_eq!(read_frame(&mut client).await.unwrap(), (FIN | BINARY, largest));
+    client.write_all(&frame(FIN | BINARY, &[0; MAX_FRAME_SIZE + 1])).await.unwrap();
+    assert_closed_with(&mut client, close_code::MESSAGE_TOO_BIG).await;
+
+    let mut client = echo_server();
+    client.write_all(&frame(BINARY, &[0; MAX_FRAME_SIZE])).await.unwrap();
+    client.write_all(&frame(0x0, &[0; MAX_FRAME_SIZE])).await.unwrap();
+    client.write_all(&frame(FIN, &[0])).await.unwrap();
+    assert_closed_with(&mut client, close_code::MESSAGE_TOO_BIG).await;
+}
+
+#[tokio::test]
+async fn server_initiated_close() {
+    let (mut client, server) = duplex(1024);
+    let mut socket = WebSocket::from_stream(server, limits());
+    socket.close(close_code::GOING_AWAY, "restarting").await.unwrap();
+    assert!(socket.send(Message::Text("too late".to_string())).await.is_err());
+
+    let (first_byte, payload) = read_frame(&mut client).await.unwrap();
+    assert_eq!(first_byte, FIN | CLOSE);
+    assert_eq!(payload, b"\x03\xe9restarting");
+    client.write_all(&close(close_code::GOING_AWAY, b"")).await.unwrap();
+    assert_eq!(
+        socket.recv().await.unwrap().unwrap(),
+        Message::Close(Some(CloseFrame::new(close_code::GOING_AWAY, "")))
+    );
+    assert!(socket.recv().await.is_none());
+    assert!(read_frame(&mut client).await.is_none());
+}
+
+#[tokio::test]
+async fn buffered_frames_are_read_first() {
+    let (mut client, server) = duplex(1024);
+    let mut buffered = frame(FIN | TEXT, b"early");
+    buffered.extend_from_slice(&frame(FIN | TEXT, &[]));
+    let mut socket = WebSocket::from_buffered_stream(server, buffered, limits());
+    assert_eq!(socket.recv().await.unwrap().unwrap(), Message::Text("early".to_string()));
+    assert_eq!(socket.recv().await.unwrap().unwrap(), Message::Text(String::new()));
+    client.write_all(&frame(FIN | TEXT, b"later")).await.unwrap();
+    assert_eq!(socket.recv().await.unwrap().unwrap(), Message::Text("later".to_string()));
+}
+
+#[tokio::test]
+async fn load_buffered_keeps_what_follows_the_request() {
+    let mut raw = HANDSHAKE.as_bytes().to_vec();
+    raw.extend_from_slice(&frame(FIN | TEXT, b"early"));
+    let mut reader = BufReader::new(&raw[..]);
+    let request = Request::load_buffered(&mut reader, &RequestLimits::default()).await.unwrap();
+    assert_eq!(request.get_path(), "/echo");
+    assert_eq!(reader.buffer(), &frame(FIN | TEXT, b"early")[..]);
+}
+
+#[tokio::test]
+async fn websocket_route() {
+    start_server();
+    let mut stream = TcpStream::connect("127.0.0.1:8080").await.unwrap();
+    stream.write_all(HANDSHAKE.as_bytes()).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{}", head);
+    assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+    stream.write_all(&frame(FIN | TEXT, b"over tcp")).await.unwrap();
+    assert_eq!(read_frame(&mut stream).await.unwrap(), (FIN | TEXT, b"over tcp".to_vec()));
+    stream.write_all(&close(close_code::NORMAL, b"")).await.unwrap();
+    assert_eq!(read_frame(&mut stream).await.unwrap().0, FIN | CLOSE);
+    assert!(read_frame(&mut stream).await.is_none());
+}
+
+/// A client that sends its first frame along with the handshake, without
+/// waiting for the 101.
+#[tokio::test]
+async fn frames_sent_with_the_handshake() {
+    start_server();
+    let mut stream = TcpStream::connect("127.0.0.1:8080").await.unwrap();
+    let mut raw = HANDSHAKE.as_bytes().to_vec();
+    raw.extend_from_slice(&frame(FIN | TEXT, b"eager"));
+    stream.write_all(&raw).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    assert!(head.starts_with(b"HTTP/1.1 101 "));
+    assert_eq!(read_frame(&mut stream).await.unwrap(), (FIN | TEXT, b"eager".to_vec()));
+    stream.write_all(&close(close_code::NORMAL, b"")).await.unwrap();
+    assert_eq!(read_frame(&mut stream).await.unwrap().0, FIN | CLOSE);
+}